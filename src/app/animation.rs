@@ -0,0 +1,326 @@
+use crate::app::geometry::Rect;
+use crate::app::sprite;
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub enum LoopMode {
+    /// Stops on the last frame, see `Animator::is_finished`.
+    Once,
+    #[default]
+    Loop,
+    /// Plays forward then backward, without repeating the first and last frames.
+    PingPong,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Frame {
+    /// Part of the atlas texture in pixels.
+    pub source: Rect,
+    /// Seconds the frame is shown.
+    pub duration: f32,
+}
+
+/// Sequence of atlas frames, e.g. a walk cycle or the watering-can splash.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct AnimationClip {
+    pub frames: Vec<Frame>,
+    pub loop_mode: LoopMode,
+    /// Named events reported by `Animator::update` when their frame is shown, e.g. footsteps.
+    pub events: Vec<(usize, &'static str)>,
+}
+
+impl AnimationClip {
+    pub fn new(loop_mode: LoopMode) -> Self {
+        Self {
+            loop_mode,
+            ..Self::default()
+        }
+    }
+
+    /// `count` frames of the same size side by side in the atlas, starting at `first`.
+    pub fn from_strip(first: Rect, count: usize, duration: f32, loop_mode: LoopMode) -> Self {
+        let frames = (0..count)
+            .map(|index| Frame {
+                source: first.translate(index as f32 * first.width, 0.0),
+                duration,
+            })
+            .collect();
+
+        Self {
+            frames,
+            loop_mode,
+            events: Vec::new(),
+        }
+    }
+
+    pub fn with_frame(mut self, source: Rect, duration: f32) -> Self {
+        self.frames.push(Frame { source, duration });
+        self
+    }
+
+    pub fn with_event(mut self, frame: usize, name: &'static str) -> Self {
+        self.events.push((frame, name));
+        self
+    }
+
+    /// Seconds to play every frame once.
+    pub fn duration(&self) -> f32 {
+        self.frames.iter().map(|frame| frame.duration).sum()
+    }
+}
+
+/// Plays a clip for one sprite or instance, advanced every frame by `update`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Animator {
+    clip: AnimationClip,
+    frame: usize,
+    /// Seconds the current frame has been shown.
+    time: f32,
+    backwards: bool,
+    finished: bool,
+    /// Events of the current frame are reported by the next update, set when a clip starts.
+    entering: bool,
+    /// Multiplies `dt`, e.g. to walk faster.
+    pub speed: f32,
+}
+
+impl Animator {
+    pub fn new(clip: AnimationClip) -> Self {
+        Self {
+            clip,
+            frame: 0,
+            time: 0.0,
+            backwards: false,
+            finished: false,
+            entering: true,
+            speed: 1.0,
+        }
+    }
+
+    pub fn clip(&self) -> &AnimationClip {
+        &self.clip
+    }
+
+    /// Switches to `clip` from its first frame, playing the clip already running does nothing.
+    pub fn play(&mut self, clip: &AnimationClip) {
+        if self.clip != *clip {
+            self.clip = clip.clone();
+            self.restart();
+        }
+    }
+
+    pub fn restart(&mut self) {
+        self.frame = 0;
+        self.time = 0.0;
+        self.backwards = false;
+        self.finished = false;
+        self.entering = true;
+    }
+
+    pub fn frame(&self) -> usize {
+        self.frame
+    }
+
+    /// A `LoopMode::Once` clip reached the end of its last frame.
+    pub fn is_finished(&self) -> bool {
+        self.finished
+    }
+
+    /// Advances by `dt` seconds and returns the events of the frames shown meanwhile, in order.
+    pub fn update(&mut self, dt: f32) -> Vec<&'static str> {
+        let mut events = Vec::new();
+
+        if std::mem::take(&mut self.entering) {
+            self.push_events(&mut events);
+        }
+
+        // Frames without duration would never let the time run out.
+        if self.finished || self.clip.duration() <= 0.0 {
+            return events;
+        }
+
+        self.time += dt * self.speed;
+
+        while let Some(frame) = self.clip.frames.get(self.frame).copied() {
+            if self.time < frame.duration {
+                break;
+            }
+
+            let Some(next) = self.next_frame() else {
+                self.time = frame.duration;
+                self.finished = true;
+                break;
+            };
+
+            self.time -= frame.duration;
+            self.frame = next;
+            self.push_events(&mut events);
+        }
+
+        events
+    }
+
+    /// Part of the atlas to draw, see `Renderer::draw_sprite`.
+    pub fn source_rect(&self) -> Rect {
+        self.clip.frames.get(self.frame)
+            .map(|frame| frame.source)
+            .unwrap_or_default()
+    }
+
+    /// The current frame as a UV rect of an atlas of `width` by `height` pixels, see `DrawCall::with_uv_rect`.
+    pub fn uv_rect(&self, width: u32, height: u32) -> [f32; 4] {
+        sprite::uv_rect(&self.source_rect(), width, height)
+    }
+
+    /// Index of the frame after the current one, `None` once a `LoopMode::Once` clip is over.
+    fn next_frame(&mut self) -> Option<usize> {
+        let last = self.clip.frames.len().saturating_sub(1);
+
+        match self.clip.loop_mode {
+            LoopMode::Once => (self.frame < last).then_some(self.frame + 1),
+            LoopMode::Loop => Some(if self.frame < last { self.frame + 1 } else { 0 }),
+            LoopMode::PingPong if last == 0 => Some(0),
+            LoopMode::PingPong => {
+                if self.frame == last {
+                    self.backwards = true;
+                } else if self.frame == 0 {
+                    self.backwards = false;
+                }
+
+                Some(if self.backwards { self.frame - 1 } else { self.frame + 1 })
+            },
+        }
+    }
+
+    fn push_events(&self, events: &mut Vec<&'static str>) {
+        events.extend(
+            self.clip.events.iter()
+                .filter(|&&(frame, _)| frame == self.frame)
+                .map(|&(_, name)| name)
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FRAME: Rect = Rect::new(0.0, 0.0, 16.0, 16.0);
+
+    fn strip(count: usize, loop_mode: LoopMode) -> AnimationClip {
+        AnimationClip::from_strip(FRAME, count, 0.25, loop_mode)
+    }
+
+    /// Frame shown after each of `steps` updates of one frame duration.
+    fn frames(animator: &mut Animator, steps: usize) -> Vec<usize> {
+        (0..steps)
+            .map(|_| {
+                animator.update(0.25);
+                animator.frame()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn loop_wraps_to_first_frame() {
+        let mut animator = Animator::new(strip(3, LoopMode::Loop));
+
+        assert_eq!(frames(&mut animator, 5), [1, 2, 0, 1, 2]);
+        assert!(!animator.is_finished());
+    }
+
+    #[test]
+    fn once_stops_on_last_frame() {
+        let mut animator = Animator::new(strip(3, LoopMode::Once));
+
+        assert_eq!(frames(&mut animator, 2), [1, 2]);
+        assert!(!animator.is_finished());
+
+        assert_eq!(frames(&mut animator, 2), [2, 2]);
+        assert!(animator.is_finished());
+
+        animator.restart();
+        assert_eq!(animator.frame(), 0);
+        assert!(!animator.is_finished());
+    }
+
+    #[test]
+    fn ping_pong_skips_repeating_ends() {
+        let mut animator = Animator::new(strip(3, LoopMode::PingPong));
+
+        assert_eq!(frames(&mut animator, 7), [1, 2, 1, 0, 1, 2, 1]);
+        assert!(!animator.is_finished());
+    }
+
+    #[test]
+    fn ping_pong_single_frame() {
+        let mut animator = Animator::new(strip(1, LoopMode::PingPong));
+
+        assert_eq!(frames(&mut animator, 3), [0, 0, 0]);
+        assert!(!animator.is_finished());
+    }
+
+    #[test]
+    fn events_of_the_first_frame_on_first_update() {
+        let clip = strip(2, LoopMode::Loop).with_event(0, "start");
+        let mut animator = Animator::new(clip.clone());
+
+        assert_eq!(animator.update(0.0), ["start"]);
+        assert!(animator.update(0.0).is_empty());
+
+        // Playing the running clip doesn't restart it.
+        animator.play(&clip);
+        assert!(animator.update(0.0).is_empty());
+    }
+
+    #[test]
+    fn events_across_a_large_step() {
+        let clip = strip(3, LoopMode::Loop)
+            .with_event(0, "start")
+            .with_event(1, "step")
+            .with_event(2, "splash");
+        let mut animator = Animator::new(clip);
+
+        assert_eq!(animator.update(0.0), ["start"]);
+        assert_eq!(animator.update(0.8), ["step", "splash", "start"]);
+        assert_eq!(animator.frame(), 0);
+    }
+
+    #[test]
+    fn large_step_finishes_once_clip() {
+        let clip = strip(3, LoopMode::Once).with_event(2, "done");
+        let mut animator = Animator::new(clip);
+
+        assert_eq!(animator.update(10.0), ["done"]);
+        assert_eq!(animator.frame(), 2);
+        assert!(animator.is_finished());
+        assert!(animator.update(10.0).is_empty());
+    }
+
+    #[test]
+    fn speed_scales_dt() {
+        let mut animator = Animator::new(strip(4, LoopMode::Loop));
+        animator.speed = 2.0;
+
+        assert_eq!(frames(&mut animator, 2), [2, 0]);
+    }
+
+    #[test]
+    fn zero_duration_clip_stays_on_first_frame() {
+        let clip = AnimationClip::from_strip(FRAME, 3, 0.0, LoopMode::Loop).with_event(0, "start");
+        let mut animator = Animator::new(clip);
+
+        assert_eq!(animator.update(1.0), ["start"]);
+        assert!(animator.update(1.0).is_empty());
+        assert_eq!(animator.frame(), 0);
+        assert!(!animator.is_finished());
+    }
+
+    #[test]
+    fn source_rect_follows_frame() {
+        let mut animator = Animator::new(strip(3, LoopMode::Loop));
+        animator.update(0.5);
+
+        assert_eq!(animator.source_rect(), FRAME.translate(32.0, 0.0));
+        assert_eq!(animator.uv_rect(64, 16), [0.5, 0.0, 0.25, 1.0]);
+    }
+}
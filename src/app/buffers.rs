@@ -1,12 +1,13 @@
 use std::ops::Range;
 use wgpu::util::DeviceExt;
+use crate::app::geometry::Aabb;
 use crate::app::texture::Texture;
 
 pub const VERTICES: &[Vertex] = &[
-    Vertex { position: Position([-0.5, 0.5, 0.0]), tex_coords: UV([0.0, 0.0]) },
-    Vertex { position: Position([0.5, 0.5, 0.0]), tex_coords: UV([1.0, 0.0]) },
-    Vertex { position: Position([-0.5, -0.5, 0.0]), tex_coords: UV([0.0, 1.0]) },
-    Vertex { position: Position([0.5, -0.5, 0.0]), tex_coords: UV([1.0, 1.0]) }
+    Vertex { position: Position([-0.5, 0.5, 0.0]), tex_coords: UV([0.0, 0.0]), normal: Normal([0.0, 0.0, 1.0]) },
+    Vertex { position: Position([0.5, 0.5, 0.0]), tex_coords: UV([1.0, 0.0]), normal: Normal([0.0, 0.0, 1.0]) },
+    Vertex { position: Position([-0.5, -0.5, 0.0]), tex_coords: UV([0.0, 1.0]), normal: Normal([0.0, 0.0, 1.0]) },
+    Vertex { position: Position([0.5, -0.5, 0.0]), tex_coords: UV([1.0, 1.0]), normal: Normal([0.0, 0.0, 1.0]) }
 ];
 
 pub const INDICES: &[u16] = &[
@@ -20,6 +21,17 @@ pub const INDICES: &[u16] = &[
 pub struct Vertex {
     position: Position,
     tex_coords: UV,
+    normal: Normal,
+}
+
+impl Vertex {
+    pub fn new(position: [f32; 3], tex_coords: [f32; 2], normal: [f32; 3]) -> Self {
+        Self {
+            position: Position(position),
+            tex_coords: UV(tex_coords),
+            normal: Normal(normal),
+        }
+    }
 }
 
 #[repr(C)]
@@ -30,13 +42,29 @@ pub struct Position([f32;3]);
 #[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
 pub struct UV([f32;2]);
 
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct Normal([f32;3]);
+
 #[repr(C)]
 #[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
 pub struct InstanceRaw {
     model: [[f32;4];4],
+    id: u32,
+    tint: [f32;4],
+    uv_rect: [f32;4],
 }
 
 impl InstanceRaw {
+    pub fn new(model: cgmath::Matrix4<f32>, id: u32, tint: [f32; 4], uv_rect: [f32; 4]) -> Self {
+        Self {
+            model: model.into(),
+            id,
+            tint,
+            uv_rect,
+        }
+    }
+
     pub fn desc() -> wgpu::VertexBufferLayout<'static> {
         use std::mem;
 
@@ -64,6 +92,21 @@ impl InstanceRaw {
                     shader_location: 8,
                     format: wgpu::VertexFormat::Float32x4,
                 },
+                wgpu::VertexAttribute {
+                    offset: mem::size_of::<[f32; 16]>() as wgpu::BufferAddress,
+                    shader_location: 9,
+                    format: wgpu::VertexFormat::Uint32,
+                },
+                wgpu::VertexAttribute {
+                    offset: (mem::size_of::<[f32; 16]>() + mem::size_of::<u32>()) as wgpu::BufferAddress,
+                    shader_location: 10,
+                    format: wgpu::VertexFormat::Float32x4,
+                },
+                wgpu::VertexAttribute {
+                    offset: (mem::size_of::<[f32; 20]>() + mem::size_of::<u32>()) as wgpu::BufferAddress,
+                    shader_location: 11,
+                    format: wgpu::VertexFormat::Float32x4,
+                },
             ],
         }
     }
@@ -73,6 +116,7 @@ pub struct Mesh {
     vertex_buffer: wgpu::Buffer,
     index_buffer: wgpu::Buffer,
     len: u32,
+    pub bounds: Aabb,
 }
 
 impl Mesh {
@@ -87,10 +131,15 @@ impl Mesh {
             bytemuck::cast_slice(indices),
         );
 
+        let bounds = Aabb::from_points(
+            vertices.iter().map(|vertex| cgmath::Point3::from(vertex.position.0))
+        ).unwrap_or(Aabb::new(cgmath::Point3::new(0.0, 0.0, 0.0), cgmath::Point3::new(0.0, 0.0, 0.0)));
+
         Self {
             vertex_buffer,
             index_buffer,
             len: indices.len() as u32,
+            bounds,
         }
     }
 
@@ -128,7 +177,7 @@ impl Mesh {
 
 impl Vertex {
     pub fn desc() -> wgpu::VertexBufferLayout<'static> {
-        static ATTRIBUTES: [wgpu::VertexAttribute; 2] = [
+        static ATTRIBUTES: [wgpu::VertexAttribute; 3] = [
             wgpu::VertexAttribute {
                 offset: 0,
                 format: wgpu::VertexFormat::Float32x3,
@@ -139,6 +188,11 @@ impl Vertex {
                 format: wgpu::VertexFormat::Float32x2,
                 shader_location: 1,
             },
+            wgpu::VertexAttribute {
+                offset: (std::mem::size_of::<Position>() + std::mem::size_of::<UV>()) as wgpu::BufferAddress,
+                format: wgpu::VertexFormat::Float32x3,
+                shader_location: 2,
+            },
         ];
 
         wgpu::VertexBufferLayout {
@@ -0,0 +1,100 @@
+use winit::event_loop::EventLoop;
+use winit::window::{Icon, WindowBuilder};
+use crate::app::{App, GameLogic};
+use crate::app::settings::{PresentMode, Settings};
+
+pub struct AppBuilder {
+    game_logic: Box<dyn GameLogic>,
+    settings: Settings,
+    icon: Option<String>,
+}
+
+impl AppBuilder {
+    pub fn new(game_logic: Box<dyn GameLogic>) -> Self {
+        Self {
+            game_logic,
+            settings: Settings::default(),
+            icon: None,
+        }
+    }
+
+    pub fn settings(mut self, settings: Settings) -> Self {
+        self.settings = settings;
+        self
+    }
+
+    /// Overrides the current settings with the keys found in `filepath`, if it exists.
+    pub fn settings_file(mut self, filepath: &str) -> Self {
+        if let Ok(text) = std::fs::read_to_string(filepath) {
+            self.settings.read(&text);
+        }
+
+        self
+    }
+
+    pub fn title(mut self, title: &str) -> Self {
+        self.settings.title = title.to_string();
+        self
+    }
+
+    pub fn size(mut self, width: u32, height: u32) -> Self {
+        self.settings.width = width;
+        self.settings.height = height;
+        self
+    }
+
+    /// Loads shaders from the source tree and reloads shaders, textures and meshes live when they are saved.
+    pub fn dev_mode(mut self, dev_mode: bool) -> Self {
+        self.settings.dev_mode = dev_mode;
+        self
+    }
+
+    pub fn vsync(mut self, vsync: bool) -> Self {
+        self.settings.present_mode = if vsync {
+            PresentMode::Vsync
+        } else {
+            PresentMode::NoVsync
+        };
+
+        self
+    }
+
+    /// Path to an image used as the window icon.
+    pub fn icon(mut self, filepath: &str) -> Self {
+        self.icon = Some(filepath.to_string());
+        self
+    }
+
+    pub async fn run(self) {
+        let event_loop = EventLoop::new().unwrap();
+
+        let window = WindowBuilder::new()
+            .with_title(&self.settings.title)
+            .with_inner_size(self.settings.size())
+            .with_window_icon(self.icon.as_deref().and_then(Self::load_icon))
+            .build(&event_loop)
+            .unwrap();
+
+        window.set_fullscreen(self.settings.fullscreen(window.current_monitor()));
+
+        let mut app = App::new(window, self.game_logic, self.settings).await;
+
+        event_loop.run(move |event, elwt| {
+            app.main_loop(event, elwt);
+        }).unwrap();
+    }
+
+    fn load_icon(filepath: &str) -> Option<Icon> {
+        let image = match image::open(filepath) {
+            Ok(image) => image.to_rgba8(),
+            Err(err) => {
+                log::warn!("Can't load window icon {filepath}: {err}");
+                return None;
+            }
+        };
+
+        let (width, height) = image.dimensions();
+
+        Icon::from_rgba(image.into_raw(), width, height).ok()
+    }
+}
@@ -1,13 +1,82 @@
+use crate::app::geometry::Ray;
+
+#[derive(Copy, Clone)]
 pub struct Camera {
     pub eye: cgmath::Point3<f32>,
     pub target: cgmath::Point3<f32>,
     pub up: cgmath::Vector3<f32>,
     pub aspect: f32,
-    pub fov_y: f32,
+    /// Height of the viewport in physical pixels, used by pixel-perfect projections.
+    pub viewport_height: f32,
+    pub projection: Projection,
     pub z_near: f32,
     pub z_far: f32,
 }
 
+/// Matches `Camera` in the shaders: view-projection matrix and eye position for specular lighting.
+#[repr(C)]
+#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct CameraUniform {
+    view_projection: [[f32; 4]; 4],
+    eye: [f32; 4],
+}
+
+impl CameraUniform {
+    pub fn new(view_projection: cgmath::Matrix4<f32>, eye: cgmath::Point3<f32>) -> Self {
+        Self {
+            view_projection: view_projection.into(),
+            eye: [eye.x, eye.y, eye.z, 1.0],
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Projection {
+    Perspective {
+        fov_y: f32,
+    },
+    Orthographic {
+        /// World units visible vertically.
+        height: f32,
+        pixel_perfect: Option<PixelPerfect>,
+    },
+}
+
+/// Keeps pixel art crisp under an orthographic projection.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct PixelPerfect {
+    /// Texels of the sprite art per world unit.
+    pub texels_per_unit: f32,
+    /// Rounds the scale so every texel covers a whole number of screen pixels.
+    pub integer_zoom: bool,
+    /// Moves the view in whole texel steps so sprites don't shimmer while panning.
+    pub snap_to_texel: bool,
+}
+
+impl Projection {
+    pub fn perspective(fov_y: f32) -> Self {
+        Projection::Perspective { fov_y }
+    }
+
+    pub fn orthographic(height: f32) -> Self {
+        Projection::Orthographic {
+            height,
+            pixel_perfect: None,
+        }
+    }
+
+    pub fn pixel_perfect(height: f32, texels_per_unit: f32) -> Self {
+        Projection::Orthographic {
+            height,
+            pixel_perfect: Some(PixelPerfect {
+                texels_per_unit,
+                integer_zoom: true,
+                snap_to_texel: true,
+            }),
+        }
+    }
+}
+
 #[rustfmt::skip]
 pub const OPENGL_TO_WGPU_MATRIX: cgmath::Matrix4<f32> = cgmath::Matrix4::new(
     1.0, 0.0, 0.0, 0.0,
@@ -17,27 +86,97 @@ pub const OPENGL_TO_WGPU_MATRIX: cgmath::Matrix4<f32> = cgmath::Matrix4::new(
 );
 
 impl Camera {
+    pub fn to_uniform(&self) -> CameraUniform {
+        CameraUniform::new(self.calculate_matrix(), self.eye)
+    }
+
     pub fn calculate_matrix(&self) -> cgmath::Matrix4<f32> {
-        let view = cgmath::Matrix4::look_at_rh(self.eye, self.target, self.up);
+        let mut view = cgmath::Matrix4::look_at_rh(self.eye, self.target, self.up);
 
-        let proj = cgmath::perspective(
-            cgmath::Deg(self.fov_y),
+        let proj = match self.projection {
+            Projection::Perspective { fov_y } => cgmath::perspective(
+                cgmath::Deg(fov_y),
                 self.aspect,
                 self.z_near,
                 self.z_far
-        );
+            ),
+
+            Projection::Orthographic { height, pixel_perfect } => {
+                let height = match pixel_perfect {
+                    Some(pixel_perfect) => {
+                        if pixel_perfect.snap_to_texel {
+                            let texel = 1.0 / pixel_perfect.texels_per_unit;
+                            view.w.x = (view.w.x / texel).round() * texel;
+                            view.w.y = (view.w.y / texel).round() * texel;
+                        }
+
+                        self.pixel_perfect_height(height, pixel_perfect)
+                    },
+                    None => height,
+                };
+
+                let half_height = height / 2.0;
+                let half_width = half_height * self.aspect;
+
+                cgmath::ortho(
+                    -half_width,
+                    half_width,
+                    -half_height,
+                    half_height,
+                    self.z_near,
+                    self.z_far
+                )
+            },
+        };
 
         OPENGL_TO_WGPU_MATRIX * proj * view
     }
 
-    #[allow(unused)]
+    /// World-space ray under `cursor`, given in pixels of a `width` x `height` viewport.
+    pub fn screen_ray(&self, cursor: cgmath::Point2<f32>, width: u32, height: u32) -> Option<Ray> {
+        Ray::from_screen(
+            cursor,
+            cgmath::Vector2::new(width as f32, height as f32),
+            &self.calculate_matrix(),
+        )
+    }
+
+    pub fn set_viewport(&mut self, width: u32, height: u32) {
+        if width > 0 && height > 0 {
+            self.aspect = width as f32 / height as f32;
+            self.viewport_height = height as f32;
+        }
+    }
+
+    /// Visible height after rounding the screen pixels per texel to a whole number.
+    fn pixel_perfect_height(&self, height: f32, pixel_perfect: PixelPerfect) -> f32 {
+        if !pixel_perfect.integer_zoom || self.viewport_height <= 0.0 {
+            return height;
+        }
+
+        let texels = height * pixel_perfect.texels_per_unit;
+        let zoom = (self.viewport_height / texels).round().max(1.0);
+
+        self.viewport_height / (zoom * pixel_perfect.texels_per_unit)
+    }
+
+    /// Places the eye around the target, `coords.x` is the yaw and `coords.y` the pitch in radians.
     pub fn set_angles(&mut self, coords: cgmath::Point2<f32>) {
         const DISTANCE : f32 = 15.0;
 
-        self.eye = cgmath::Point3::new(
-            DISTANCE * coords.x.cos(),
-            0.0,
-            DISTANCE * coords.x.sin(),
-        );
+        self.orbit(coords.x, coords.y, DISTANCE);
+    }
+
+    pub fn orbit(&mut self, yaw: f32, pitch: f32, distance: f32) {
+        self.eye = self.target + Self::direction(yaw, pitch) * distance;
+    }
+
+    /// Unit vector pointing from the target towards the eye for the given angles.
+    pub fn direction(yaw: f32, pitch: f32) -> cgmath::Vector3<f32> {
+        cgmath::Vector3::new(
+            pitch.cos() * yaw.cos(),
+            pitch.sin(),
+            pitch.cos() * yaw.sin(),
+        )
     }
 }
@@ -0,0 +1,206 @@
+use std::f32::consts::FRAC_PI_2;
+use cgmath::InnerSpace;
+use winit::event::MouseButton;
+use crate::app::camera::Camera;
+use crate::app::input::Input;
+
+pub trait CameraController {
+    fn update(&mut self, camera: &mut Camera, input: &Input, dt: f32);
+}
+
+/// Rotates around the camera target while a mouse button is held and zooms with the wheel.
+pub struct OrbitController {
+    pub yaw: f32,
+    pub pitch: f32,
+    pub distance: f32,
+    pub min_distance: f32,
+    pub max_distance: f32,
+    /// Radians per pixel of mouse movement.
+    pub rotate_speed: f32,
+    /// Fraction of the distance zoomed per scroll line.
+    pub zoom_speed: f32,
+    pub button: MouseButton,
+}
+
+impl OrbitController {
+    pub fn new(yaw: f32, pitch: f32, distance: f32) -> Self {
+        Self {
+            yaw,
+            pitch,
+            distance,
+            min_distance: 2.0,
+            max_distance: 80.0,
+            rotate_speed: 0.005,
+            zoom_speed: 0.1,
+            button: MouseButton::Left,
+        }
+    }
+
+    /// Starts from the current eye position so the camera doesn't jump.
+    pub fn from_camera(camera: &Camera) -> Self {
+        let offset = camera.eye - camera.target;
+        let distance = offset.magnitude();
+        let pitch = (offset.y / distance).asin();
+        let yaw = offset.z.atan2(offset.x);
+
+        Self::new(yaw, pitch, distance)
+    }
+}
+
+impl CameraController for OrbitController {
+    fn update(&mut self, camera: &mut Camera, input: &Input, _dt: f32) {
+        const MAX_PITCH: f32 = FRAC_PI_2 - 0.01;
+
+        if input.is_mouse_down(self.button) {
+            let delta = input.mouse_delta();
+
+            self.yaw += delta.x * self.rotate_speed;
+            self.pitch = (self.pitch + delta.y * self.rotate_speed).clamp(-MAX_PITCH, MAX_PITCH);
+        }
+
+        self.distance = (self.distance * (1.0 - input.scroll() * self.zoom_speed))
+            .clamp(self.min_distance, self.max_distance);
+
+        camera.orbit(self.yaw, self.pitch, self.distance);
+    }
+}
+
+/// Smooth third-person camera that trails behind a target set by the game every frame.
+pub struct FollowController {
+    pub target: cgmath::Point3<f32>,
+    /// Eye position relative to the target.
+    pub offset: cgmath::Vector3<f32>,
+    /// Height above the target position the camera looks at.
+    pub look_height: f32,
+    /// How fast the camera catches up, higher is snappier.
+    pub stiffness: f32,
+}
+
+impl FollowController {
+    pub fn new(target: cgmath::Point3<f32>, offset: cgmath::Vector3<f32>) -> Self {
+        Self {
+            target,
+            offset,
+            look_height: 1.0,
+            stiffness: 5.0,
+        }
+    }
+
+    pub fn set_target(&mut self, target: cgmath::Point3<f32>) {
+        self.target = target;
+    }
+}
+
+impl CameraController for FollowController {
+    fn update(&mut self, camera: &mut Camera, _input: &Input, dt: f32) {
+        let look_at = self.target + cgmath::Vector3::unit_y() * self.look_height;
+        let desired_eye = self.target + self.offset;
+
+        // Frame rate independent exponential smoothing.
+        let t = 1.0 - (-self.stiffness * dt).exp();
+
+        camera.eye = camera.eye + (desired_eye - camera.eye) * t;
+        camera.target = camera.target + (look_at - camera.target) * t;
+    }
+}
+
+/// Fixed-angle farm camera that pans over the ground when the cursor touches the window edges.
+pub struct TopDownController {
+    /// Point on the ground the camera looks at.
+    pub focus: cgmath::Point3<f32>,
+    pub yaw: f32,
+    pub pitch: f32,
+    pub distance: f32,
+    pub min_distance: f32,
+    pub max_distance: f32,
+    pub zoom_speed: f32,
+    /// World units per second.
+    pub pan_speed: f32,
+    /// Width of the edge panning area in pixels.
+    pub edge_margin: f32,
+    /// Map bounds on the XZ plane the focus point is clamped to.
+    pub bounds: Option<(cgmath::Point2<f32>, cgmath::Point2<f32>)>,
+}
+
+impl TopDownController {
+    pub fn new(focus: cgmath::Point3<f32>) -> Self {
+        Self {
+            focus,
+            yaw: FRAC_PI_2,
+            pitch: 55f32.to_radians(),
+            distance: 20.0,
+            min_distance: 5.0,
+            max_distance: 40.0,
+            zoom_speed: 0.1,
+            pan_speed: 10.0,
+            edge_margin: 16.0,
+            bounds: None,
+        }
+    }
+
+    /// Classic isometric angles: 45 degree yaw and ~35 degree pitch.
+    pub fn isometric(focus: cgmath::Point3<f32>) -> Self {
+        Self {
+            yaw: std::f32::consts::FRAC_PI_4,
+            pitch: (1.0 / 2f32.sqrt()).atan(),
+            ..Self::new(focus)
+        }
+    }
+
+    pub fn with_bounds(mut self, min: cgmath::Point2<f32>, max: cgmath::Point2<f32>) -> Self {
+        self.bounds = Some((min, max));
+        self
+    }
+
+    /// -1, 0 or 1 on each axis depending on which window edges the cursor touches.
+    fn edge_direction(&self, input: &Input) -> cgmath::Vector2<f32> {
+        let Some(cursor) = input.cursor() else {
+            return cgmath::Vector2::new(0.0, 0.0);
+        };
+
+        let size = input.window_size();
+        let (width, height) = (size.width as f32, size.height as f32);
+
+        let axis = |position: f32, length: f32| {
+            if position < self.edge_margin {
+                -1.0
+            } else if position > length - self.edge_margin {
+                1.0
+            } else {
+                0.0
+            }
+        };
+
+        cgmath::Vector2::new(axis(cursor.x, width), axis(cursor.y, height))
+    }
+
+    fn clamp_focus(&mut self) {
+        if let Some((min, max)) = self.bounds {
+            self.focus.x = self.focus.x.clamp(min.x, max.x);
+            self.focus.z = self.focus.z.clamp(min.y, max.y);
+        }
+    }
+}
+
+impl CameraController for TopDownController {
+    fn update(&mut self, camera: &mut Camera, input: &Input, dt: f32) {
+        let back = Camera::direction(self.yaw, 0.0);
+        let forward = -back;
+        let right = forward.cross(cgmath::Vector3::unit_y());
+
+        let edge = self.edge_direction(input);
+        let pan = right * edge.x - forward * edge.y;
+
+        if pan.magnitude2() > 0.0 {
+            self.focus += pan.normalize() * self.pan_speed * dt;
+        }
+
+        self.clamp_focus();
+
+        self.distance = (self.distance * (1.0 - input.scroll() * self.zoom_speed))
+            .clamp(self.min_distance, self.max_distance);
+
+        camera.target = self.focus;
+        camera.eye = self.focus + Camera::direction(self.yaw, self.pitch) * self.distance;
+    }
+}
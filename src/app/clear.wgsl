@@ -0,0 +1,16 @@
+// Fills the viewport with the blend constant and resets its depth, so views drawn
+// over another one only clear their own part of the target.
+
+// One triangle covering the whole viewport on the far plane, no vertex buffer needed.
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> @builtin(position) vec4<f32> {
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+
+    return vec4<f32>(uv * vec2<f32>(2.0, -2.0) + vec2<f32>(-1.0, 1.0), 1.0, 1.0);
+}
+
+// Multiplied by the blend constant, which holds the clear color of the pass.
+@fragment
+fn fs_main() -> @location(0) vec4<f32> {
+    return vec4<f32>(1.0);
+}
@@ -0,0 +1,38 @@
+// Inputs shared by every pass drawing meshes, must match `Vertex::desc` and `InstanceRaw::desc`.
+
+struct VertexInput {
+    @location(0) position: vec3<f32>,
+    @location(1) tex_coords: vec2<f32>,
+    @location(2) normal: vec3<f32>,
+};
+
+struct InstanceInput {
+    @location(5) model_matrix_0: vec4<f32>,
+    @location(6) model_matrix_1: vec4<f32>,
+    @location(7) model_matrix_2: vec4<f32>,
+    @location(8) model_matrix_3: vec4<f32>,
+    @location(9) id: u32,
+#ifdef INSTANCE_TINT
+    @location(10) tint: vec4<f32>,
+#endif
+    // Offset and size of the part of the texture drawn, e.g. a glyph in the font atlas.
+    @location(11) uv_rect: vec4<f32>,
+}
+
+struct Camera {
+    view_projection: mat4x4<f32>,
+    eye: vec4<f32>,
+};
+
+fn instance_tex_coords(tex_coords: vec2<f32>, instance: InstanceInput) -> vec2<f32> {
+    return instance.uv_rect.xy + tex_coords * instance.uv_rect.zw;
+}
+
+fn model_matrix(instance: InstanceInput) -> mat4x4<f32> {
+    return mat4x4<f32> (
+        instance.model_matrix_0,
+        instance.model_matrix_1,
+        instance.model_matrix_2,
+        instance.model_matrix_3,
+    );
+}
@@ -1,30 +1,116 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::default::Default;
 use std::fs::File;
 use std::io::Read;
+use std::path::{Path, PathBuf};
 use wgpu::{Buffer, PowerPreference, RequestAdapterOptions, StoreOp};
 use wgpu::util::DeviceExt;
 use winit::window::Window;
 use crate::app::GameLogic;
-use crate::app::buffers::{InstanceRaw, Mesh, Vertex};
-use crate::app::camera::Camera;
-use crate::app::matrix::MatrixUniform;
-use crate::app::texture::{DepthTexture, Texture};
+use crate::app::buffers::{INDICES, InstanceRaw, Mesh, VERTICES, Vertex};
+use crate::app::camera::{Camera, CameraUniform, OPENGL_TO_WGPU_MATRIX, Projection};
+use crate::app::camera_controller::CameraController;
+use crate::app::debug_draw::{DebugDraw, LineBuffer};
+use crate::app::geometry::{Frustum, Ray, Rect};
+use crate::app::hot_reload::{FileWatcher, validated};
+use crate::app::input::Input;
+use crate::app::obj;
+use crate::app::light::{Lights, LightsRaw};
+use crate::app::picking::Picking;
+use crate::app::pipeline::{BlendMode, DepthMode, PipelineCache, PipelineKey, VertexLayout};
+use crate::app::post::{HDR_FORMAT, Lut, PostEffect, PostProcessing};
+use crate::app::render_mode::{DepthView, RenderMode};
+use crate::app::settings::{Settings, WindowMode};
+use crate::app::shader::{Preprocessor, ShaderFeatures};
+use crate::app::shadow::ShadowMap;
+use crate::app::sprite::{self, SpriteCamera};
+use crate::app::text::{Font, TextStyle};
+use crate::app::time_of_day::{DayNight, GameClock};
+use crate::app::texture::{DepthTexture, MultisampledTexture, RenderTarget, Texture};
+use crate::app::uniform::Uniform;
+use crate::app::view::{View, ViewTarget, Viewport};
 
 #[derive(Copy, Clone, PartialOrd, PartialEq, Hash, Eq)]
 pub struct DrawParams {
     pub mesh_id: usize,
     pub texture_id: usize,
+    pub features: ShaderFeatures,
 }
 
+impl DrawParams {
+    pub fn new(mesh_id: usize, texture_id: usize) -> Self {
+        Self {
+            mesh_id,
+            texture_id,
+            features: ShaderFeatures::default(),
+        }
+    }
+
+    pub fn with_features(mut self, features: ShaderFeatures) -> Self {
+        self.features = features;
+        self
+    }
+}
+
+#[derive(Copy, Clone)]
 pub struct DrawCall {
     pub params: DrawParams,
     pub matrix: cgmath::Matrix4<f32>,
+    /// Multiplied with the texture color when `ShaderFeatures::instance_tint` is set.
+    pub tint: [f32; 4],
+    /// Offset and size of the part of the texture drawn, the whole texture by default.
+    pub uv_rect: [f32; 4],
+}
+
+impl DrawCall {
+    pub fn new(params: DrawParams, matrix: cgmath::Matrix4<f32>) -> Self {
+        Self {
+            params,
+            matrix,
+            tint: [1.0; 4],
+            uv_rect: [0.0, 0.0, 1.0, 1.0],
+        }
+    }
+
+    /// Tints this instance, e.g. to highlight the crop under the cursor. Enables `instance_tint`.
+    pub fn with_tint(mut self, tint: [f32; 4]) -> Self {
+        self.params.features.instance_tint = true;
+        self.tint = tint;
+        self
+    }
+
+    /// Draws only part of the texture, e.g. a glyph of a font atlas or a sprite of a sheet.
+    pub fn with_uv_rect(mut self, uv_rect: [f32; 4]) -> Self {
+        self.uv_rect = uv_rect;
+        self
+    }
 }
 
 pub struct DrawCallInstanced {
     pub params: DrawParams,
-    pub instances: Vec<cgmath::Matrix4<f32>>
+    pub instances: Vec<cgmath::Matrix4<f32>>,
+    pub ids: Vec<u32>,
+    pub tints: Vec<[f32; 4]>,
+    pub uv_rects: Vec<[f32; 4]>,
+}
+
+impl DrawCallInstanced {
+    fn new(params: DrawParams) -> Self {
+        Self {
+            params,
+            instances: Vec::new(),
+            ids: Vec::new(),
+            tints: Vec::new(),
+            uv_rects: Vec::new(),
+        }
+    }
+
+    fn push(&mut self, id: usize, draw: &DrawCall) {
+        self.instances.push(draw.matrix);
+        self.ids.push(id as u32);
+        self.tints.push(draw.tint);
+        self.uv_rects.push(draw.uv_rect);
+    }
 }
 
 pub struct RawDrawCallInstanced {
@@ -33,20 +119,76 @@ pub struct RawDrawCallInstanced {
     pub range: u32,
 }
 
+struct PassTarget<'t> {
+    color: &'t wgpu::TextureView,
+    resolve_target: Option<&'t wgpu::TextureView>,
+    depth: &'t wgpu::TextureView,
+    /// Clears the whole target, `None` keeps what previous views drew.
+    clear_color: Option<wgpu::Color>,
+    /// Clears only the viewport, used by views drawn over the main one.
+    viewport_clear: Option<wgpu::Color>,
+    viewport: [f32; 4],
+    skip_texture: Option<usize>,
+}
+
+const SCENE_SHADER: &str = "shader.wgsl";
+const CLEAR_SHADER: &str = "clear.wgsl";
+const DEBUG_SHADER: &str = "debug.wgsl";
+
+/// Post shader reloaded in dev mode, read from `Settings::shader_directory`.
+const POST_SHADER_FILE: &str = "post.wgsl";
+
+pub struct RayHit {
+    pub params: DrawParams,
+    pub matrix: cgmath::Matrix4<f32>,
+    pub distance: f32,
+    pub point: cgmath::Point3<f32>,
+}
+
+/// Draw call found under a pixel by the GPU picking pass.
+#[derive(Copy, Clone)]
+pub struct PickResult {
+    /// Value returned by `Renderer::draw` in the frame the pick was requested.
+    pub id: u32,
+    pub params: DrawParams,
+    pub matrix: cgmath::Matrix4<f32>,
+}
+
+/// Counters of the previous frame.
+#[derive(Copy, Clone, Default, Debug)]
+pub struct RenderStats {
+    pub draw_calls: usize,
+    pub drawn_instances: usize,
+    pub culled_instances: usize,
+}
+
 pub struct Renderer<'a> {
     context: &'a mut Context,
-    pub batches: HashMap<DrawParams, DrawCallInstanced>,
+    draws: Vec<DrawCall>,
+    /// Drawn in pixels over the finished frame, in the order they were queued.
+    overlay: Vec<DrawCall>,
+    overlay_clip: Option<Rect>,
+    /// Sprites and their layer, sorted by layer before drawing.
+    sprites: Vec<(i32, DrawCall)>,
 }
 
 impl<'a> Renderer<'a> {
-    fn new<'b>(context: &'b mut Context) -> Renderer<'a> where 'b : 'a {
+    fn new(context: &'a mut Context) -> Renderer<'a> {
         Renderer {
             context,
-            batches: HashMap::new(),
+            draws: Vec::new(),
+            overlay: Vec::new(),
+            overlay_clip: None,
+            sprites: Vec::new(),
         }
     }
 
+    /// Loads a texture once per path; later calls with the same path return the same id,
+    /// so scenes can share assets without passing ids around.
     pub fn add_texture(&mut self, filepath: &str) -> Option<usize> {
+        if let Some(&id) = self.context.texture_paths.get(filepath) {
+            return Some(id);
+        }
 
         let mut f = File::open(filepath).ok()?;
         let mut buffer = Vec::new();
@@ -54,16 +196,20 @@ impl<'a> Renderer<'a> {
         // read the whole file
         f.read_to_end(&mut buffer).ok()?;
 
-        let texture = Texture::new(
+        let texture = Texture::try_new(
             buffer.as_slice(),
-            "Texture",
+            filepath,
             &self.context.device,
             &self.context.queue
-        );
-
+        )?;
 
         self.context.textures.push(texture);
-        Some(self.context.textures.len() - 1)
+
+        let id = self.context.textures.len() - 1;
+        self.context.texture_paths.insert(filepath.to_string(), id);
+        self.context.watch(filepath);
+
+        Some(id)
     }
 
     pub fn add_mesh(&mut self, vertices: &[Vertex], indices: &[u16]) -> usize {
@@ -77,24 +223,368 @@ impl<'a> Renderer<'a> {
         self.context.meshes.len() - 1
     }
 
-    pub fn draw(&mut self, draw_call: DrawCall) {
+    /// Loads a Wavefront OBJ mesh, see `obj::parse` for what is supported.
+    pub fn load_mesh(&mut self, filepath: &str) -> Option<usize> {
+        if let Some(&id) = self.context.mesh_paths.get(filepath) {
+            return Some(id);
+        }
 
-        let key = draw_call.params;
+        let mesh = Context::read_mesh(&self.context.device, Path::new(filepath))?;
 
-        if self.batches.contains_key(&key) {
-            self.batches.get_mut(&key)
-                .unwrap()
-                .instances.push(draw_call.matrix);
-        } else {
-            self.batches.insert(
-                key,
-                DrawCallInstanced {
-                    params: key,
-                    instances: vec![draw_call.matrix],
-                }
+        self.context.meshes.push(mesh);
+
+        let id = self.context.meshes.len() - 1;
+        self.context.mesh_paths.insert(filepath.to_string(), id);
+        self.context.watch(filepath);
+
+        Some(id)
+    }
+
+    /// Loads a TTF or OTF font, glyphs are rasterized at `pixel_size` the first time they are drawn.
+    pub fn add_font(&mut self, filepath: &str, pixel_size: f32) -> Option<usize> {
+        let bytes = std::fs::read(filepath).ok()?;
+
+        let context = &mut *self.context;
+        let font = Font::new(bytes, pixel_size, context.textures.len())?;
+
+        let texture = Texture::from_rgba(
+            &vec![0; (Font::ATLAS_SIZE * Font::ATLAS_SIZE * 4) as usize],
+            Font::ATLAS_SIZE,
+            Font::ATLAS_SIZE,
+            filepath,
+            &context.device,
+            &context.queue,
+        );
+
+        context.textures.push(texture);
+        context.fonts.push(font);
+
+        Some(context.fonts.len() - 1)
+    }
+
+    /// Width and height of `text` in the units of `style.size`.
+    pub fn measure_text(&self, font: usize, text: &str, style: &TextStyle) -> [f32; 2] {
+        self.context.fonts[font].measure(text, style)
+    }
+
+    /// Draws `text` over the scene, `position` is the top of the first line in pixels
+    /// from the top left corner of the window, see `TextAlign` for the horizontal anchor.
+    pub fn draw_text(&mut self, font: usize, text: &str, position: [f32; 2], style: &TextStyle) {
+        let font = &mut self.context.fonts[font];
+        let texture_id = font.texture_id;
+
+        for quad in font.layout(text, style) {
+            let rect = Rect::new(position[0] + quad.min[0], position[1] + quad.min[1], quad.size[0], quad.size[1]);
+            self.draw_image(texture_id, rect, quad.uv_rect, style.color);
+        }
+    }
+
+    /// Fills `rect` in pixels over the scene.
+    pub fn draw_rect(&mut self, rect: Rect, color: [f32; 4]) {
+        self.draw_image(self.context.white_texture, rect, [0.0, 0.0, 1.0, 1.0], color);
+    }
+
+    /// Draws the `uv_rect` part of a texture stretched over `rect` in pixels, over the scene.
+    pub fn draw_image(&mut self, texture_id: usize, rect: Rect, uv_rect: [f32; 4], tint: [f32; 4]) {
+        if rect.width <= 0.0 || rect.height <= 0.0 {
+            return;
+        }
+
+        let Some(clipped) = self.overlay_clip.map_or(Some(rect), |clip| rect.intersection(&clip)) else {
+            return;
+        };
+
+        // Crops the texture by the same fraction as the rectangle.
+        let [u, v, width, height] = uv_rect;
+        let uv_rect = [
+            u + (clipped.x - rect.x) / rect.width * width,
+            v + (clipped.y - rect.y) / rect.height * height,
+            clipped.width / rect.width * width,
+            clipped.height / rect.height * height,
+        ];
+
+        let params = DrawParams::new(self.context.quad_mesh, texture_id)
+            .with_features(ShaderFeatures {
+                instance_tint: true,
+                ..ShaderFeatures::unlit()
+            });
+
+        self.overlay.push(
+            DrawCall::new(params, sprite::quad_matrix(&clipped, 0.0))
+                .with_tint(tint)
+                .with_uv_rect(uv_rect)
+        );
+    }
+
+    /// Draws the `src_rect` part of a texture over `dst_rect`, both in pixels, `None` draws the whole texture.
+    /// Sprites go through `SpriteCamera`, are rotated clockwise in radians around their center
+    /// and drawn over the scene from the lowest layer up, in the order they were queued within a layer.
+    pub fn draw_sprite(
+        &mut self,
+        texture_id: usize,
+        src_rect: Option<Rect>,
+        dst_rect: Rect,
+        rotation: f32,
+        tint: [f32; 4],
+        layer: i32,
+    ) {
+        let uv_rect = match src_rect {
+            Some(src_rect) => {
+                let (width, height) = self.context.textures[texture_id].size();
+                sprite::uv_rect(&src_rect, width, height)
+            },
+            None => [0.0, 0.0, 1.0, 1.0],
+        };
+
+        let params = DrawParams::new(self.context.quad_mesh, texture_id)
+            .with_features(ShaderFeatures {
+                instance_tint: true,
+                ..ShaderFeatures::unlit()
+            });
+
+        let draw = DrawCall::new(params, sprite::quad_matrix(&dst_rect, rotation))
+            .with_tint(tint)
+            .with_uv_rect(uv_rect);
+
+        self.sprites.push((layer, draw));
+    }
+
+    pub fn sprite_camera(&self) -> &SpriteCamera {
+        &self.context.sprite_camera
+    }
+
+    pub fn sprite_camera_mut(&mut self) -> &mut SpriteCamera {
+        &mut self.context.sprite_camera
+    }
+
+    /// Width and height in pixels, e.g. to split a sprite sheet into frames.
+    pub fn texture_size(&self, texture_id: usize) -> (u32, u32) {
+        self.context.textures[texture_id].size()
+    }
+
+    /// Later overlay draws are cut to `clip`, e.g. the visible part of a scroll list.
+    pub fn set_overlay_clip(&mut self, clip: Option<Rect>) {
+        self.overlay_clip = clip;
+    }
+
+    pub fn overlay_clip(&self) -> Option<Rect> {
+        self.overlay_clip
+    }
+
+    /// Draws `text` in the XY plane of `matrix` with the scene, e.g. signs and name tags.
+    /// `style.size` is in world units and the first line starts at the origin going down.
+    pub fn draw_text_3d(&mut self, font: usize, text: &str, matrix: cgmath::Matrix4<f32>, style: &TextStyle) {
+        let context = &mut *self.context;
+        let font = &mut context.fonts[font];
+
+        // Alpha tested so the glyphs sort with the scene without blending.
+        let params = DrawParams::new(context.quad_mesh, font.texture_id)
+            .with_features(ShaderFeatures {
+                alpha_test: true,
+                instance_tint: true,
+                ..ShaderFeatures::unlit()
+            });
+
+        for quad in font.layout(text, style) {
+            let center = cgmath::Vector3::new(
+                quad.min[0] + quad.size[0] / 2.0,
+                -(quad.min[1] + quad.size[1] / 2.0),
+                0.0,
+            );
+
+            let glyph_matrix = matrix
+                * cgmath::Matrix4::from_translation(center)
+                * cgmath::Matrix4::from_nonuniform_scale(quad.size[0], quad.size[1], 1.0);
+
+            self.draws.push(
+                DrawCall::new(params, glyph_matrix)
+                    .with_tint(style.color)
+                    .with_uv_rect(quad.uv_rect)
             );
         }
     }
+
+    pub fn settings(&self) -> &Settings {
+        self.context.settings()
+    }
+
+    pub fn camera(&self) -> &Camera {
+        &self.context.camera
+    }
+
+    pub fn camera_mut(&mut self) -> &mut Camera {
+        &mut self.context.camera
+    }
+
+    pub fn input(&self) -> &Input {
+        &self.context.input
+    }
+
+    /// Seconds since the previous frame.
+    pub fn delta_time(&self) -> f32 {
+        self.context.delta_time
+    }
+
+    /// World-space ray under the mouse cursor.
+    pub fn cursor_ray(&self) -> Option<Ray> {
+        let input = &self.context.input;
+        let size = input.window_size();
+        let [x, y, width, height] = self.context.main_viewport.to_pixels(size.width, size.height);
+
+        let cursor = input.cursor()? - cgmath::Vector2::new(x, y);
+        self.context.camera.screen_ray(cursor, width as u32, height as u32)
+    }
+
+    /// Closest instance drawn so far this frame whose mesh bounds the ray hits.
+    pub fn raycast(&self, ray: &Ray) -> Option<RayHit> {
+        self.draws.iter()
+            .filter_map(|draw| {
+                let bounds = &self.context.meshes[draw.params.mesh_id].bounds;
+
+                ray.intersect_transformed_aabb(bounds, &draw.matrix)
+                    .map(|distance| RayHit {
+                        params: draw.params,
+                        matrix: draw.matrix,
+                        distance,
+                        point: ray.at(distance),
+                    })
+            })
+            .min_by(|a, b| a.distance.total_cmp(&b.distance))
+    }
+
+    /// Renders an id pass this frame and reads back the draw call under pixel `(x, y)`.
+    /// The pixel is read back without waiting for the GPU, so the result shows up in `pick_result` a frame or more later.
+    pub fn request_pick(&mut self, x: u32, y: u32) {
+        self.context.pick_request = Some((x, y));
+    }
+
+    /// Result of a pick read back since the previous frame, `None` if nothing was hit or no result arrived yet.
+    pub fn pick_result(&self) -> Option<PickResult> {
+        self.context.pick_result
+    }
+
+    /// Creates an offscreen target and returns its texture id, usable both in
+    /// `ViewTarget::Texture` and in `DrawParams::texture_id`.
+    pub fn add_render_target(&mut self, width: u32, height: u32) -> usize {
+        let context = &mut *self.context;
+
+        let (render_target, texture) = RenderTarget::new(
+            &context.device,
+            &Context::hdr_config(&context.config),
+            width,
+            height,
+            context.sample_count,
+        );
+
+        context.textures.push(texture);
+
+        let id = context.textures.len() - 1;
+        context.render_targets.insert(id, render_target);
+
+        id
+    }
+
+    /// Adds a camera rendered after the main one and returns its index.
+    pub fn add_view(&mut self, view: View) -> usize {
+        self.context.views.push(view);
+        self.context.views.len() - 1
+    }
+
+    pub fn view_mut(&mut self, index: usize) -> Option<&mut View> {
+        self.context.views.get_mut(index)
+    }
+
+    /// Part of the window used by the main camera, e.g. the left half for split-screen.
+    pub fn set_main_viewport(&mut self, viewport: Viewport) {
+        self.context.main_viewport = viewport;
+    }
+
+    pub fn stats(&self) -> RenderStats {
+        self.context.stats
+    }
+
+    /// Lines, boxes and labels drawn over the scene this frame, see `DebugDraw::set_enabled`.
+    pub fn debug(&mut self) -> &mut DebugDraw {
+        self.context.debug_draw.face_camera(&self.context.camera);
+        &mut self.context.debug_draw
+    }
+
+    pub fn render_mode(&self) -> RenderMode {
+        self.context.render_mode
+    }
+
+    pub fn supports_render_mode(&self, mode: RenderMode) -> bool {
+        self.context.supports_render_mode(mode)
+    }
+
+    pub fn set_render_mode(&mut self, mode: RenderMode) {
+        self.context.set_render_mode(mode);
+    }
+
+    /// Frustum culling skips instances whose mesh bounds are outside the camera view.
+    pub fn set_culling(&mut self, enabled: bool) {
+        self.context.culling = enabled;
+    }
+
+    pub fn update_camera(&mut self, controller: &mut dyn CameraController) {
+        let context = &mut *self.context;
+        controller.update(&mut context.camera, &context.input, context.delta_time);
+    }
+
+    pub fn apply_settings(&mut self, settings: Settings) {
+        self.context.apply_settings(settings);
+    }
+
+    /// Loads a color grading LUT strip for `PostEffect::ColorGrading`, see `Lut::from_strip`.
+    pub fn add_lut(&mut self, filepath: &str) -> Option<usize> {
+        let bytes = std::fs::read(filepath).ok()?;
+
+        let context = &mut *self.context;
+        let lut = Lut::from_strip(&bytes, &context.device, &context.queue, context.post.lut_layout())?;
+
+        Some(context.post.add_lut(lut))
+    }
+
+    /// Post-processing passes run in order on the HDR frame, before the screen fade.
+    pub fn post_effects_mut(&mut self) -> &mut Vec<PostEffect> {
+        &mut self.context.post.effects
+    }
+
+    /// Blends the whole frame towards `color` by `amount` in `0.0..=1.0`.
+    pub fn set_fade(&mut self, color: [f32; 3], amount: f32) {
+        let [r, g, b] = color;
+        self.context.fade = [r, g, b, amount.clamp(0.0, 1.0)];
+    }
+
+    pub fn lights(&self) -> &Lights {
+        &self.context.lights
+    }
+
+    /// Sun, ambient and point lights used by every view this frame.
+    pub fn lights_mut(&mut self) -> &mut Lights {
+        &mut self.context.lights
+    }
+
+    pub fn clock(&self) -> &GameClock {
+        &self.context.day_night.clock
+    }
+
+    pub fn clock_mut(&mut self) -> &mut GameClock {
+        &mut self.context.day_night.clock
+    }
+
+    /// Sky gradient, sunrise and sunset, applied to the lights before every frame.
+    pub fn day_night_mut(&mut self) -> &mut DayNight {
+        &mut self.context.day_night
+    }
+
+    /// Queues the draw call and returns its id for this frame, used by picking.
+    pub fn draw(&mut self, draw_call: DrawCall) -> u32 {
+        let id = self.draws.len() as u32;
+        self.draws.push(draw_call);
+
+        id
+    }
 }
 
 pub struct Context {
@@ -104,19 +594,62 @@ pub struct Context {
     queue: wgpu::Queue,
     config: wgpu::SurfaceConfiguration,
     size: winit::dpi::PhysicalSize<u32>,
-    pipeline: wgpu::RenderPipeline,
+    settings: Settings,
+    present_modes: Vec<wgpu::PresentMode>,
+    sample_counts: Vec<u32>,
+    sample_count: u32,
+    pipelines: PipelineCache,
     pub camera: Camera,
+    input: Input,
+    delta_time: f32,
 
     meshes: Vec<Mesh>,
     textures: Vec<Texture>,
+    /// Unit quad in the XY plane used for glyphs and overlay rectangles.
+    quad_mesh: usize,
+    /// Single white texel, tinted to fill overlay rectangles.
+    white_texture: usize,
+    fonts: Vec<Font>,
+    sprite_camera: SpriteCamera,
+    /// Projections of the sprite and overlay passes, updated every frame.
+    sprite_camera_uniform: Uniform<CameraUniform>,
+    overlay_camera: Uniform<CameraUniform>,
+    texture_paths: HashMap<String, usize>,
+    mesh_paths: HashMap<String, usize>,
+
+    fade: [f32; 4],
+    post: PostProcessing,
+    file_watcher: Option<FileWatcher>,
+    /// Shader sources watched in dev mode.
+    shader_paths: HashSet<PathBuf>,
+    lights: Lights,
+    lights_uniform: Uniform<LightsRaw>,
+    shadow_map: ShadowMap,
+    day_night: DayNight,
 
     draw_calls: Vec<RawDrawCallInstanced>,
+    debug_draw: DebugDraw,
+    debug_lines: Option<LineBuffer>,
+    render_mode: RenderMode,
+    depth_view: DepthView,
+    culling: bool,
+    stats: RenderStats,
+
+    main_viewport: Viewport,
+    views: Vec<View>,
+    render_targets: HashMap<usize, RenderTarget>,
 
     depth_texture: DepthTexture,
+    msaa_texture: Option<MultisampledTexture>,
+
+    picking: Option<Picking>,
+    pick_request: Option<(u32, u32)>,
+    pick_result: Option<PickResult>,
+    pick_draws: Vec<DrawCall>,
 }
 
 impl Context {
-    pub async fn new(window: Window) -> Self {
+    pub async fn new(window: Window, settings: Settings) -> Self {
         let size = window.inner_size();
         let instance = wgpu::Instance::default();
         let surface = unsafe { instance.create_surface(&window) }.unwrap();
@@ -132,7 +665,10 @@ impl Context {
 
         let (device, queue) = adapter.request_device(
             &wgpu::DeviceDescriptor {
-                features: wgpu::Features::empty(),
+                // Optional: adapter specific format features allow MSAA sample counts other
+                // than 4, line polygon mode is only used by the wireframe render mode.
+                features: adapter.features()
+                    & (wgpu::Features::TEXTURE_ADAPTER_SPECIFIC_FORMAT_FEATURES | wgpu::Features::POLYGON_MODE_LINE),
 
                 ..Default::default()
             },
@@ -141,55 +677,511 @@ impl Context {
             .await
             .expect("Failed to request device");
 
-        let config = surface.get_default_config(&adapter, size.width, size.height).unwrap();
+        let present_modes = surface.get_capabilities(&adapter).present_modes;
+
+        // A minimized window reports a zero size, which the surface can't be configured with.
+        let mut config = surface.get_default_config(&adapter, size.width.max(1), size.height.max(1)).unwrap();
+        config.present_mode = settings.present_mode.to_wgpu(&present_modes);
 
         surface.configure(&device, &config);
 
-        let first_shader = device.create_shader_module(
-            wgpu::include_wgsl!("shader.wgsl")
+        let sample_counts = Self::supported_sample_counts(&adapter, &device, HDR_FORMAT);
+        let sample_count = Self::pick_sample_count(&sample_counts, settings.msaa_samples);
+
+        if sample_count != settings.msaa_samples {
+            log::warn!("MSAA x{} is not supported, using x{sample_count}", settings.msaa_samples);
+        }
+
+        let camera_layout = Uniform::<CameraUniform>::create_bind_group_layout(
+            &device,
+            wgpu::ShaderStages::VERTEX_FRAGMENT,
+            "Camera",
+        );
+
+        let lights = Lights::default();
+        let origin = cgmath::Point3::new(0.0, 0.0, 0.0);
+
+        let lights_uniform = Uniform::new(
+            &device,
+            lights.to_raw(origin, lights.shadow_matrix(origin, ShadowMap::RESOLUTION), ShadowMap::RESOLUTION),
+            wgpu::ShaderStages::FRAGMENT,
+            "Lights",
         );
 
-        let matrix_uniform = MatrixUniform::new(&device);
+        let shadow_map = ShadowMap::new(&device, &lights_uniform.buffer);
 
-        let depth_texture = DepthTexture::new(&device, &config, "depth texture");
+        let sprite_camera_uniform = Uniform::<CameraUniform>::new(
+            &device,
+            bytemuck::Zeroable::zeroed(),
+            wgpu::ShaderStages::VERTEX_FRAGMENT,
+            "Sprite camera",
+        );
 
-        let pipeline = Context::create_render_pipeline(
+        let overlay_camera = Uniform::<CameraUniform>::new(
             &device,
-            &config,
-            first_shader,
-            &[&Texture::create_bind_group_layout(&device), &matrix_uniform.layout],
+            bytemuck::Zeroable::zeroed(),
+            wgpu::ShaderStages::VERTEX_FRAGMENT,
+            "Overlay camera",
+        );
+
+        let depth_texture = DepthTexture::new(&device, &config, sample_count, "depth texture");
+        let msaa_texture = Self::create_msaa_texture(&device, &config, sample_count);
+
+        let scene_layout = device.create_pipeline_layout(
+            &wgpu::PipelineLayoutDescriptor {
+                label: Some("Render pipeline"),
+                bind_group_layouts: &[
+                    &Texture::create_bind_group_layout(&device),
+                    &camera_layout,
+                    &shadow_map.layout,
+                ],
+                push_constant_ranges: &[],
+            }
+        );
+
+        let mut pipelines = PipelineCache::new(Preprocessor::builtin());
+        pipelines.add_layout(SCENE_SHADER, scene_layout);
+        pipelines.add_layout(ShadowMap::pipeline_key().shader, ShadowMap::create_pipeline_layout(&device, &camera_layout));
+        pipelines.add_layout(Picking::pipeline_key().shader, Picking::create_pipeline_layout(&device, &camera_layout));
+
+        let clear_layout = device.create_pipeline_layout(
+            &wgpu::PipelineLayoutDescriptor {
+                label: Some("Viewport clear pipeline"),
+                bind_group_layouts: &[],
+                push_constant_ranges: &[],
+            }
+        );
+
+        pipelines.add_layout(CLEAR_SHADER, clear_layout);
+
+        let debug_layout = device.create_pipeline_layout(
+            &wgpu::PipelineLayoutDescriptor {
+                label: Some("Debug line pipeline"),
+                bind_group_layouts: &[&camera_layout],
+                push_constant_ranges: &[],
+            }
         );
 
+        pipelines.add_layout(DEBUG_SHADER, debug_layout);
+
+        let depth_view = DepthView::new(&device, sample_count);
+        pipelines.add_layout(DepthView::SHADER, depth_view.create_pipeline_layout(&device));
+
+        let post = PostProcessing::new(&device, &queue, &config);
+
         let camera = Camera {
             eye: (0.0, 10.0, 20.0).into(),
             target: (0.0, 0.0, 0.0).into(),
             up: cgmath::Vector3::unit_y(),
             aspect: config.width as f32 / config.height as f32,
-            fov_y: 45.0,
+            viewport_height: config.height as f32,
+            projection: Projection::perspective(45.0),
             z_near: 0.1,
             z_far: 100.0,
         };
 
-        Context {
+        let shader_directory = settings.dev_mode.then(|| PathBuf::from(&settings.shader_directory));
+
+        let mut context = Context {
             window,
             surface,
             device,
             queue,
             config,
             size,
-            pipeline,
+            settings,
+            present_modes,
+            sample_counts,
+            sample_count,
+            pipelines,
             camera,
+            input: Input::new(size),
+            delta_time: 0.0,
             meshes: vec![],
             textures: vec![],
+            quad_mesh: 0,
+            white_texture: 0,
+            fonts: vec![],
+            sprite_camera: SpriteCamera::default(),
+            sprite_camera_uniform,
+            overlay_camera,
+            texture_paths: HashMap::new(),
+            mesh_paths: HashMap::new(),
+            fade: [0.0; 4],
+            post,
+            file_watcher: None,
+            shader_paths: HashSet::new(),
+            lights,
+            lights_uniform,
+            shadow_map,
+            day_night: DayNight::default(),
             depth_texture,
-            draw_calls: vec![]
-        }
+            msaa_texture,
+            draw_calls: vec![],
+            debug_draw: DebugDraw::new(false),
+            debug_lines: None,
+            render_mode: RenderMode::default(),
+            depth_view,
+            culling: true,
+            stats: RenderStats::default(),
+            main_viewport: Viewport::FULL,
+            views: vec![],
+            render_targets: HashMap::new(),
+            picking: None,
+            pick_request: None,
+            pick_result: None,
+            pick_draws: Vec::new(),
+        };
+
+        context.meshes.push(Mesh::new(&context.device, VERTICES, INDICES));
+        context.quad_mesh = context.meshes.len() - 1;
+
+        let white = Texture::from_rgba(&[255; 4], 1, 1, "white texture", &context.device, &context.queue);
+        context.textures.push(white);
+        context.white_texture = context.textures.len() - 1;
+
+        context.set_hot_reload(shader_directory);
+        context
     }
 
     pub fn window(&self) -> &Window {
         &self.window
     }
 
+    pub fn settings(&self) -> &Settings {
+        &self.settings
+    }
+
+    pub fn input_mut(&mut self) -> &mut Input {
+        &mut self.input
+    }
+
+    pub fn clock(&self) -> &GameClock {
+        &self.day_night.clock
+    }
+
+    /// Moves the game clock forward, returns how many new hours have started.
+    pub fn advance_clock(&mut self, dt: f32) -> u32 {
+        self.day_night.clock.advance(dt)
+    }
+
+    /// Applies changed settings to the window and reconfigures the surface if needed.
+    pub fn apply_settings(&mut self, settings: Settings) {
+        if settings.title != self.settings.title {
+            self.window.set_title(&settings.title);
+        }
+
+        if settings.window_mode != self.settings.window_mode || settings.size() != self.settings.size() {
+            self.window.set_fullscreen(settings.fullscreen(self.window.current_monitor()));
+
+            if settings.window_mode == WindowMode::Windowed {
+                let _ = self.window.request_inner_size(settings.size());
+            }
+        }
+
+        if settings.present_mode != self.settings.present_mode {
+            self.config.present_mode = settings.present_mode.to_wgpu(&self.present_modes);
+            self.surface.configure(&self.device, &self.config);
+        }
+
+        let sample_count = Self::pick_sample_count(&self.sample_counts, settings.msaa_samples);
+
+        if sample_count != self.sample_count {
+            if sample_count != settings.msaa_samples {
+                log::warn!("MSAA x{} is not supported, using x{sample_count}", settings.msaa_samples);
+            }
+
+            self.sample_count = sample_count;
+
+            // The depth texture switches between multisampled and regular bindings.
+            self.depth_view = DepthView::new(&self.device, sample_count);
+            self.pipelines.add_layout(DepthView::SHADER, self.depth_view.create_pipeline_layout(&self.device));
+
+            // Keys hold the sample count, the old pipelines would never be used again
+            // but still be recompiled on every shader reload.
+            self.pipelines.clear();
+
+            self.create_render_targets();
+        }
+
+        if settings.dev_mode != self.settings.dev_mode || settings.shader_directory != self.settings.shader_directory {
+            self.set_hot_reload(settings.dev_mode.then(|| PathBuf::from(&settings.shader_directory)));
+        }
+
+        self.settings = settings;
+    }
+
+    /// Starts watching the shader sources in `directory`, loaded textures and meshes,
+    /// and loads the current version of the shaders, `None` stops watching.
+    fn set_hot_reload(&mut self, directory: Option<PathBuf>) {
+        self.shader_paths.clear();
+
+        let Some(directory) = directory else {
+            self.file_watcher = None;
+            self.pipelines.set_preprocessor(Preprocessor::builtin());
+            self.pipelines.clear();
+            return;
+        };
+
+        let mut watcher = FileWatcher::new(std::time::Duration::from_millis(250));
+        self.pipelines.set_preprocessor(Preprocessor::from_directory(&directory));
+
+        for file in Preprocessor::builtin_files().chain([POST_SHADER_FILE]) {
+            let path = directory.join(file);
+            watcher.watch(&path);
+            self.shader_paths.insert(path);
+        }
+
+        self.reload_shader(&directory.join(POST_SHADER_FILE));
+
+        if let Err(error) = self.pipelines.rebuild(&self.device) {
+            log::error!("Shaders in {} are invalid, keeping the previous pipelines:\n{error}", directory.display());
+        }
+
+        for path in self.texture_paths.keys().chain(self.mesh_paths.keys()) {
+            watcher.watch(path);
+        }
+
+        self.file_watcher = Some(watcher);
+    }
+
+    fn watch(&mut self, path: &str) {
+        if let Some(watcher) = &mut self.file_watcher {
+            watcher.watch(path);
+        }
+    }
+
+    fn reload_changed_files(&mut self) {
+        let Some(watcher) = &mut self.file_watcher else {
+            return;
+        };
+
+        for path in watcher.poll() {
+            let key = path.to_string_lossy();
+
+            if let Some(&id) = self.texture_paths.get(key.as_ref()) {
+                self.reload_texture(&path, id);
+            } else if let Some(&id) = self.mesh_paths.get(key.as_ref()) {
+                self.reload_mesh(&path, id);
+            } else if self.shader_paths.contains(&path) {
+                self.reload_shader(&path);
+            } else {
+                log::warn!("{} changed but isn't a watched shader, texture or mesh", path.display());
+            }
+        }
+    }
+
+    /// Replaces the texture in place, so ids held by the game stay valid.
+    fn reload_texture(&mut self, path: &Path, id: usize) {
+        let texture = std::fs::read(path).ok()
+            .and_then(|bytes| Texture::try_new(&bytes, &path.to_string_lossy(), &self.device, &self.queue));
+
+        match texture {
+            Some(texture) => {
+                self.textures[id] = texture;
+                log::info!("Reloaded {}", path.display());
+            },
+            // Often the file is still being written, the next save triggers another reload.
+            None => log::warn!("Can't reload texture {}, keeping the previous version", path.display()),
+        }
+    }
+
+    fn reload_mesh(&mut self, path: &Path, id: usize) {
+        match Self::read_mesh(&self.device, path) {
+            Some(mesh) => {
+                self.meshes[id] = mesh;
+                log::info!("Reloaded {}", path.display());
+            },
+            None => log::warn!("Can't reload mesh {}, keeping the previous version", path.display()),
+        }
+    }
+
+    fn read_mesh(device: &wgpu::Device, path: &Path) -> Option<Mesh> {
+        let text = std::fs::read_to_string(path).ok()?;
+        let (vertices, indices) = obj::parse(&text)?;
+
+        Some(Mesh::new(device, &vertices, &indices))
+    }
+
+    /// Recompiles the shader at `path` and rebuilds its pipelines, any other path rebuilds
+    /// every cached pipeline. On errors the last working pipelines are kept and the error is logged.
+    fn reload_shader(&mut self, path: &Path) {
+        let result = if path.file_name().and_then(|name| name.to_str()) == Some(POST_SHADER_FILE) {
+            self.reload_post_shader(path)
+        } else {
+            self.pipelines.rebuild(&self.device)
+        };
+
+        match result {
+            Ok(()) => log::info!("Reloaded {}", path.display()),
+            Err(error) => log::error!("Shader {} is invalid, keeping the previous version:\n{error}", path.display()),
+        }
+    }
+
+    fn reload_post_shader(&mut self, path: &Path) -> Result<(), String> {
+        let source = std::fs::read_to_string(path).map_err(|error| error.to_string())?;
+        let device = &self.device;
+
+        let shader = validated(device, || {
+            device.create_shader_module(wgpu::ShaderModuleDescriptor {
+                label: path.file_name().and_then(|name| name.to_str()),
+                source: wgpu::ShaderSource::Wgsl(source.into()),
+            })
+        }).map_err(|error| error.to_string())?;
+
+        self.post.set_shader(device, &shader, self.config.format)
+            .map_err(|error| error.to_string())
+    }
+
+    /// Builds the pipelines used this frame that aren't cached yet.
+    fn prepare_pipelines(&mut self, draws: &[DrawCall], sprites: &[DrawCall], overlay: &[DrawCall]) {
+        let mut keys: HashSet<_> = draws.iter()
+            .map(|draw| self.scene_pipeline_key(draw.params.features))
+            .chain(sprites.iter().map(|draw| self.quad_pipeline_key(HDR_FORMAT, draw.params.features)))
+            .chain(overlay.iter().map(|draw| self.quad_pipeline_key(self.config.format, draw.params.features)))
+            .collect();
+
+        if self.lights.shadows.enabled {
+            keys.insert(ShadowMap::pipeline_key());
+        }
+
+        if self.pick_request.is_some() {
+            keys.insert(Picking::pipeline_key());
+        }
+
+        if self.views.iter().any(|view| view.enabled && matches!(view.target, ViewTarget::Screen(_))) {
+            keys.insert(self.clear_pipeline_key());
+        }
+
+        if self.debug_lines.is_some() {
+            keys.insert(self.debug_pipeline_key());
+        }
+
+        if self.render_mode == RenderMode::Depth {
+            keys.insert(self.depth_view.pipeline_key());
+        }
+
+        for key in &keys {
+            self.pipelines.prepare(&self.device, key);
+        }
+    }
+
+    fn scene_pipeline_key(&self, features: ShaderFeatures) -> PipelineKey {
+        let key = PipelineKey::new(SCENE_SHADER, Some(HDR_FORMAT))
+            .with_features(features)
+            .with_sample_count(self.sample_count);
+
+        self.render_mode.apply(key)
+    }
+
+    /// Sprites and the overlay are blended without depth, render modes don't apply to them.
+    fn quad_pipeline_key(&self, format: wgpu::TextureFormat, features: ShaderFeatures) -> PipelineKey {
+        PipelineKey::new(SCENE_SHADER, Some(format))
+            .with_features(features)
+            .with_blend(BlendMode::Alpha)
+            .with_depth(DepthMode::Disabled)
+    }
+
+    /// Wireframe needs line polygon mode, which not every adapter has.
+    pub fn supports_render_mode(&self, mode: RenderMode) -> bool {
+        mode != RenderMode::Wireframe || self.device.features().contains(wgpu::Features::POLYGON_MODE_LINE)
+    }
+
+    /// Unsupported modes are ignored with a warning.
+    pub fn set_render_mode(&mut self, mode: RenderMode) {
+        if !self.supports_render_mode(mode) {
+            log::warn!("{mode:?} render mode is not supported by the adapter");
+            return;
+        }
+
+        self.render_mode = mode;
+    }
+
+    /// Fills the viewport with the blend constant and resets its depth.
+    fn clear_pipeline_key(&self) -> PipelineKey {
+        PipelineKey::new(CLEAR_SHADER, Some(HDR_FORMAT))
+            .with_vertex_layout(VertexLayout::Fullscreen)
+            .with_blend(BlendMode::Constant)
+            .with_depth(DepthMode::Reset)
+            .with_sample_count(self.sample_count)
+    }
+
+    fn debug_pipeline_key(&self) -> PipelineKey {
+        PipelineKey::new(DEBUG_SHADER, Some(HDR_FORMAT))
+            .with_vertex_layout(VertexLayout::Line)
+            .with_topology(wgpu::PrimitiveTopology::LineList)
+            .with_blend(BlendMode::Alpha)
+            .with_depth(DepthMode::Overlay)
+            .with_sample_count(self.sample_count)
+    }
+
+    /// Sample counts usable for both the HDR scene format and the depth format. Without
+    /// adapter specific format features the device only allows the guaranteed ones.
+    fn supported_sample_counts(adapter: &wgpu::Adapter, device: &wgpu::Device, format: wgpu::TextureFormat) -> Vec<u32> {
+        let features = device.features();
+
+        let format_flags = |format: wgpu::TextureFormat| {
+            if features.contains(wgpu::Features::TEXTURE_ADAPTER_SPECIFIC_FORMAT_FEATURES) {
+                adapter.get_texture_format_features(format).flags
+            } else {
+                format.guaranteed_format_features(features).flags
+            }
+        };
+
+        let color = format_flags(format);
+        let depth = format_flags(DepthTexture::DEPTH_FORMAT);
+
+        [1, 2, 4, 8].into_iter()
+            .filter(|&count| {
+                color.sample_count_supported(count) && depth.sample_count_supported(count)
+            })
+            .collect()
+    }
+
+    /// Highest supported sample count that doesn't exceed the requested one.
+    fn pick_sample_count(supported: &[u32], requested: u32) -> u32 {
+        supported.iter()
+            .copied()
+            .filter(|&count| count <= requested)
+            .max()
+            .unwrap_or(1)
+    }
+
+    fn create_msaa_texture(
+        device: &wgpu::Device,
+        config: &wgpu::SurfaceConfiguration,
+        sample_count: u32,
+    ) -> Option<MultisampledTexture> {
+        (sample_count > 1).then(|| {
+            MultisampledTexture::new(device, &Self::hdr_config(config), sample_count, "msaa texture")
+        })
+    }
+
+    /// Surface configuration with the format of the targets the scene is rendered into.
+    fn hdr_config(config: &wgpu::SurfaceConfiguration) -> wgpu::SurfaceConfiguration {
+        wgpu::SurfaceConfiguration {
+            format: HDR_FORMAT,
+            ..config.clone()
+        }
+    }
+
+    fn create_render_targets(&mut self) {
+        self.depth_texture = DepthTexture::new(
+            &self.device,
+            &self.config,
+            self.sample_count,
+            "depth texture"
+        );
+
+        self.msaa_texture = Self::create_msaa_texture(&self.device, &self.config, self.sample_count);
+
+        for render_target in self.render_targets.values_mut() {
+            render_target.set_sample_count(&self.device, self.sample_count);
+        }
+
+        self.post.resize(&self.device, &self.config);
+    }
+
     pub fn resize(&mut self, new_size: winit::dpi::PhysicalSize<u32>) {
         if new_size.width > 0 && new_size.height > 0 {
             self.size = new_size;
@@ -197,11 +1189,8 @@ impl Context {
             self.config.height = new_size.height;
 
             self.surface.configure(&self.device, &self.config);
-            self.depth_texture = DepthTexture::new(
-                &self.device,
-                &self.config,
-                "depth texture"
-            );
+            self.create_render_targets();
+            self.camera.set_viewport(new_size.width, new_size.height);
         }
     }
 
@@ -209,22 +1198,74 @@ impl Context {
         game_logic.init(&mut Renderer::new(self));
     }
 
-    pub fn render(&mut self, game_logic: &mut dyn GameLogic) -> Result<(), wgpu::SurfaceError> {
+    /// Groups draws inside the `view_projection` frustum by mesh and texture so each group becomes one instanced draw.
+    /// Draws sampling `skip_texture` are left out, a render target can't be drawn into itself.
+    fn batch(
+        &self,
+        draws: &[DrawCall],
+        view_projection: &cgmath::Matrix4<f32>,
+        skip_texture: Option<usize>,
+        stats: &mut RenderStats,
+    ) -> Vec<DrawCallInstanced> {
+        let frustum = Frustum::from_matrix(view_projection);
+        let mut batches = HashMap::new();
+
+        for (id, draw) in draws.iter().enumerate() {
+            if Some(draw.params.texture_id) == skip_texture {
+                continue;
+            }
 
-        let batches = {
-            let mut renderer = Renderer::new(self);
-            game_logic.render(&mut renderer);
-            renderer.batches
-        };
+            if self.culling {
+                let bounds = self.meshes[draw.params.mesh_id].bounds.transform(&draw.matrix);
+
+                if !frustum.intersects_aabb(&bounds) {
+                    stats.culled_instances += 1;
+                    continue;
+                }
+            }
+
+            batches.entry(draw.params)
+                .or_insert_with(|| DrawCallInstanced::new(draw.params))
+                .push(id, draw);
+
+            stats.drawn_instances += 1;
+        }
 
-        self.draw_calls = batches.values()
-            .map( |DrawCallInstanced { params, instances }| {
+        stats.draw_calls += batches.len();
+
+        batches.into_values().collect()
+    }
+
+    /// Merges consecutive draws with the same params only, so blended draws keep their order.
+    fn batch_in_order(&self, draws: &[DrawCall]) -> Vec<DrawCallInstanced> {
+        let mut batches: Vec<DrawCallInstanced> = Vec::new();
+
+        for (id, draw) in draws.iter().enumerate() {
+            match batches.last_mut() {
+                Some(batch) if batch.params == draw.params => batch.push(id, draw),
+                _ => {
+                    let mut batch = DrawCallInstanced::new(draw.params);
+                    batch.push(id, draw);
+                    batches.push(batch);
+                },
+            }
+        }
+
+        batches
+    }
+
+    fn upload(&self, batches: Vec<DrawCallInstanced>) -> Vec<RawDrawCallInstanced> {
+        batches.iter()
+            .map( |DrawCallInstanced { params, instances, ids, tints, uv_rects }| {
 
                 let range = instances.len() as u32;
 
-                let raw_instances = instances.into_iter()
-                    .map(|m| (*m).into())
-                    .collect::<Vec<[[f32;4];4]>>();
+                let raw_instances = instances.iter()
+                    .zip(ids)
+                    .zip(tints)
+                    .zip(uv_rects)
+                    .map(|(((m, id), tint), uv_rect)| InstanceRaw::new(*m, *id, *tint, *uv_rect))
+                    .collect::<Vec<InstanceRaw>>();
 
                 let buffer = self.device.create_buffer_init(
                     &wgpu::util::BufferInitDescriptor {
@@ -240,144 +1281,415 @@ impl Context {
                     range,
                 }
             })
-            .collect();
-
-        let output = self.surface.get_current_texture()?;
+            .collect()
+    }
 
-        let view = output.texture.create_view(
-            &wgpu::TextureViewDescriptor::default()
+    /// Culls and uploads the draws for one camera and records its render pass.
+    fn render_view(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        draws: &[DrawCall],
+        camera: &Camera,
+        target: &PassTarget,
+        stats: &mut RenderStats,
+    ) -> (Vec<RawDrawCallInstanced>, Uniform<CameraUniform>) {
+        let batches = self.batch(draws, &camera.calculate_matrix(), target.skip_texture, stats);
+        let draw_calls = self.upload(batches);
+
+        let camera_uniform = Uniform::new(
+            &self.device,
+            camera.to_uniform(),
+            wgpu::ShaderStages::VERTEX_FRAGMENT,
+            "Camera",
         );
 
-        self.camera.aspect = self.config.width as f32 / self.config.height as f32;
+        let color_attachment = wgpu::RenderPassColorAttachment {
+            view: target.color,
+            resolve_target: target.resolve_target,
+            ops: wgpu::Operations {
+                load: match target.clear_color {
+                    Some(color) => wgpu::LoadOp::Clear(color),
+                    None => wgpu::LoadOp::Load,
+                },
+                store: StoreOp::Store,
+            },
+        };
 
+        let depth_stencil_attachment = wgpu::RenderPassDepthStencilAttachment {
+            view: target.depth,
+            depth_ops: Some(
+                wgpu::Operations {
+                    load: match target.clear_color {
+                        Some(_) => wgpu::LoadOp::Clear(1.0),
+                        None => wgpu::LoadOp::Load,
+                    },
+                    store: StoreOp::Store,
+                },
+            ),
+            stencil_ops: None,
+        };
 
-        let mut encoder = self.device.create_command_encoder(
-            &wgpu::CommandEncoderDescriptor {
-                label: Some("Render encoder")
-            }
-        );
+        let descriptor = wgpu::RenderPassDescriptor {
+            label: Some("Render Pass"),
+            color_attachments: &[
+                Some(color_attachment)
+            ],
+            depth_stencil_attachment: Some(depth_stencil_attachment),
+            occlusion_query_set: None,
+            timestamp_writes: None,
+        };
 
         {
-            let color_attachment = wgpu::RenderPassColorAttachment {
-                view: &view,
-                resolve_target: None,
-                ops: wgpu::Operations {
-                    load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
-                    store: StoreOp::Store,
-                },
-            };
+            let mut render_pass = encoder.begin_render_pass(&descriptor);
 
-            let depth_stencil_attachment = wgpu::RenderPassDepthStencilAttachment {
-                view: &self.depth_texture.view,
-                depth_ops: Some(
-                    wgpu::Operations {
-                        load: wgpu::LoadOp::Clear(1.0),
-                        store: StoreOp::Store,
-                    },
-                ),
-                stencil_ops: None,
-            };
+            let [x, y, width, height] = target.viewport;
+            render_pass.set_viewport(x, y, width, height, 0.0, 1.0);
 
-            let descriptor = wgpu::RenderPassDescriptor {
-                label: Some("Render Pass"),
-                color_attachments: &[
-                    Some(color_attachment)
-                ],
-                depth_stencil_attachment: Some(depth_stencil_attachment),
-                occlusion_query_set: None,
-                timestamp_writes: None,
-            };
+            let clear_pipeline = self.pipelines.get(&self.clear_pipeline_key());
 
-            let camera_matrix = self.camera.calculate_matrix();
-            let mut camera_uniform = MatrixUniform::new(&self.device);
-            camera_uniform.update(camera_matrix, &self.queue);
+            if let (Some(color), Some(pipeline)) = (target.viewport_clear, clear_pipeline) {
+                render_pass.set_pipeline(pipeline);
+                render_pass.set_blend_constant(color);
+                render_pass.draw(0..3, 0..1);
+            }
 
-            let mut render_pass = encoder.begin_render_pass(&descriptor);
-            render_pass.set_pipeline(&self.pipeline);
+            let mut current_features = None;
 
+            for draw_call in &draw_calls {
+                let features = draw_call.params.features;
+
+                if current_features != Some(features) {
+                    // Permutations that failed to build were already logged by the cache.
+                    let Some(pipeline) = self.pipelines.get(&self.scene_pipeline_key(features)) else {
+                        continue;
+                    };
+
+                    render_pass.set_pipeline(pipeline);
+                    current_features = Some(features);
+                }
 
-            for draw_call in &self.draw_calls {
                 let texture = &self.textures[draw_call.params.texture_id];
                 let mesh = &self.meshes[draw_call.params.mesh_id];
 
                 render_pass.set_vertex_buffer(1, draw_call.buffer.slice(..));
                 render_pass.set_bind_group(1, &camera_uniform.bind_group, &[]);
+                render_pass.set_bind_group(2, &self.shadow_map.bind_group, &[]);
                 mesh.draw(texture, &mut render_pass, 0..draw_call.range);
             }
+
+            let debug_pipeline = self.pipelines.get(&self.debug_pipeline_key());
+
+            if let (Some(lines), Some(pipeline)) = (&self.debug_lines, debug_pipeline) {
+                render_pass.set_pipeline(pipeline);
+                render_pass.set_bind_group(0, &camera_uniform.bind_group, &[]);
+                lines.draw(&mut render_pass);
+            }
         }
 
-        self.queue.submit(Some(encoder.finish()));
+        (draw_calls, camera_uniform)
+    }
 
-        output.present();
+    fn render_shadows(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        draws: &[DrawCall],
+        shadow_matrix: cgmath::Matrix4<f32>,
+    ) {
+        let Some(pipeline) = self.pipelines.get(&ShadowMap::pipeline_key()) else {
+            return;
+        };
 
-        Ok(())
+        // Casters culled by the sun don't count towards the frame stats.
+        let batches = self.batch(draws, &shadow_matrix, None, &mut RenderStats::default());
+        let draw_calls = self.upload(batches);
+
+        let light_uniform = Uniform::new(
+            &self.device,
+            CameraUniform::new(shadow_matrix, self.camera.target),
+            wgpu::ShaderStages::VERTEX_FRAGMENT,
+            "Shadow camera",
+        );
+
+        self.shadow_map.render(
+            encoder,
+            pipeline,
+            &draw_calls,
+            &self.meshes,
+            &self.textures,
+            &light_uniform.bind_group,
+        );
     }
 
-    fn create_render_pipeline(
-        device: &wgpu::Device,
-        config: &wgpu::SurfaceConfiguration,
-        shader: wgpu::ShaderModule,
-        bind_groups: &[&wgpu::BindGroupLayout],
-    ) -> wgpu::RenderPipeline {
-        let pipeline_layout = device.create_pipeline_layout(
-            &wgpu::PipelineLayoutDescriptor {
-                label: Some("Render pipeline"),
-                bind_group_layouts: bind_groups,
-                push_constant_ranges: &[],
+    /// Draws blended quads in order onto `output` without depth, used by sprites and the overlay.
+    /// Each caller passes its own `camera`, queue writes to a shared one would all land before the first pass.
+    fn render_quads(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        output: &wgpu::TextureView,
+        format: wgpu::TextureFormat,
+        draws: &[DrawCall],
+        camera: &Uniform<CameraUniform>,
+        label: &str,
+    ) {
+        if draws.is_empty() {
+            return;
+        }
+
+        let draw_calls = self.upload(self.batch_in_order(draws));
+
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some(label),
+            color_attachments: &[
+                Some(wgpu::RenderPassColorAttachment {
+                    view: output,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Load,
+                        store: StoreOp::Store,
+                    },
+                })
+            ],
+            depth_stencil_attachment: None,
+            occlusion_query_set: None,
+            timestamp_writes: None,
+        });
+
+        for draw_call in &draw_calls {
+            let Some(pipeline) = self.pipelines.get(&self.quad_pipeline_key(format, draw_call.params.features)) else {
+                continue;
+            };
+
+            let texture = &self.textures[draw_call.params.texture_id];
+            let mesh = &self.meshes[draw_call.params.mesh_id];
+
+            render_pass.set_pipeline(pipeline);
+            render_pass.set_vertex_buffer(1, draw_call.buffer.slice(..));
+            render_pass.set_bind_group(1, &camera.bind_group, &[]);
+            render_pass.set_bind_group(2, &self.shadow_map.bind_group, &[]);
+            mesh.draw(texture, &mut render_pass, 0..draw_call.range);
+        }
+    }
+
+    /// Uploads the atlas rows of the glyphs rasterized this frame.
+    fn upload_font_atlases(&mut self) {
+        for font in &mut self.fonts {
+            let texture_id = font.texture_id;
+
+            if let Some((rows, pixels)) = font.atlas_changes() {
+                self.textures[texture_id].write_rows(&self.queue, rows, pixels);
             }
-        );
+        }
+    }
 
-        let target_state = wgpu::ColorTargetState {
-            format: config.format,
-            blend: Some(wgpu::BlendState::REPLACE),
-            write_mask: wgpu::ColorWrites::ALL,
-        };
+    /// Renders the views drawing into textures, so they can be sampled by the window passes.
+    fn render_offscreen_views(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        draws: &[DrawCall],
+        stats: &mut RenderStats,
+    ) {
+        for view in self.views.iter().filter(|view| view.enabled) {
+            let ViewTarget::Texture(texture_id) = view.target else {
+                continue;
+            };
 
-        let vertex_state = wgpu::VertexState {
-            module: &shader,
-            entry_point: "vs_main",
-            buffers: &[Vertex::desc(), InstanceRaw::desc()],
-        };
+            let Some(render_target) = self.render_targets.get(&texture_id) else {
+                continue;
+            };
+
+            let (color, resolve_target) = match &render_target.msaa_texture {
+                Some(msaa) => (&msaa.view, Some(&render_target.view)),
+                None => (&render_target.view, None),
+            };
+
+            let target = PassTarget {
+                color,
+                resolve_target,
+                depth: &render_target.depth_texture.view,
+                clear_color: Some(self.render_mode.clear_color(view.clear_color)),
+                viewport_clear: None,
+                viewport: Viewport::FULL.to_pixels(render_target.config.width, render_target.config.height),
+                skip_texture: Some(texture_id),
+            };
+
+            let mut camera = view.camera;
+            camera.set_viewport(render_target.config.width, render_target.config.height);
 
-        let fragment_state = wgpu::FragmentState {
-            module: &shader,
-            entry_point: "fs_main",
-            targets: &[Some(target_state)],
+            self.render_view(encoder, draws, &camera, &target, stats);
+        }
+    }
+
+    pub fn render(&mut self, game_logic: &mut dyn GameLogic, dt: f32) -> Result<(), wgpu::SurfaceError> {
+        self.reload_changed_files();
+
+        self.delta_time = dt;
+        self.day_night.apply(&mut self.lights);
+
+        let (draws, mut sprites, overlay) = {
+            let mut renderer = Renderer::new(self);
+            game_logic.render(&mut renderer);
+            (renderer.draws, renderer.sprites, renderer.overlay)
         };
 
-        let primitive_state = wgpu::PrimitiveState {
-            topology: wgpu::PrimitiveTopology::TriangleList,
-            strip_index_format: None,
-            front_face: wgpu::FrontFace::Ccw,
-            cull_mode: None,
-            polygon_mode: wgpu::PolygonMode::Fill,
-            unclipped_depth: false,
-            conservative: false,
+        // Stable, so sprites in the same layer keep their order.
+        sprites.sort_by_key(|&(layer, _)| layer);
+        let sprites: Vec<DrawCall> = sprites.into_iter().map(|(_, draw)| draw).collect();
+
+        self.upload_font_atlases();
+
+        self.debug_lines = LineBuffer::new(&self.device, self.debug_draw.vertices());
+        self.debug_draw.clear();
+
+        self.prepare_pipelines(&draws, &sprites, &overlay);
+
+        self.pick_result = None;
+
+        let shadow_matrix = self.lights.shadow_matrix(self.camera.target, ShadowMap::RESOLUTION);
+        let lights = self.lights.to_raw(self.camera.eye, shadow_matrix, ShadowMap::RESOLUTION);
+        self.lights_uniform.update(lights, &self.queue);
+
+        let mut stats = RenderStats::default();
+
+        let output = self.surface.get_current_texture()?;
+
+        let view = output.texture.create_view(
+            &wgpu::TextureViewDescriptor::default()
+        );
+
+        let mut encoder = self.device.create_command_encoder(
+            &wgpu::CommandEncoderDescriptor {
+                label: Some("Render encoder")
+            }
+        );
+
+        if self.lights.shadows.enabled {
+            self.render_shadows(&mut encoder, &draws, shadow_matrix);
+        }
+
+        self.render_offscreen_views(&mut encoder, &draws, &mut stats);
+
+        let scene_view = self.post.scene_view();
+
+        let (color, resolve_target) = match &self.msaa_texture {
+            Some(msaa) => (&msaa.view, Some(scene_view)),
+            None => (scene_view, None),
         };
 
-        let depth_stencil = wgpu::DepthStencilState {
-            format: DepthTexture::DEPTH_FORMAT,
-            depth_write_enabled: true,
-            depth_compare: wgpu::CompareFunction::Less,
-            stencil: wgpu::StencilState::default(),
-            bias: wgpu::DepthBiasState::default(),
+        let mut screen_target = PassTarget {
+            color,
+            resolve_target,
+            depth: &self.depth_texture.view,
+            clear_color: Some(self.render_mode.clear_color(self.day_night.sky_color())),
+            viewport_clear: None,
+            viewport: self.main_viewport.to_pixels(self.config.width, self.config.height),
+            skip_texture: None,
         };
 
-        device.create_render_pipeline(
-            &wgpu::RenderPipelineDescriptor {
-                label: Some("Render pipeline"),
-                layout: Some(&pipeline_layout),
-                vertex: vertex_state,
-                fragment: Some(fragment_state),
-                primitive: primitive_state,
-                depth_stencil: Some(depth_stencil),
-                multisample: wgpu::MultisampleState {
-                    count: 1,
-                    mask: !0,
-                    alpha_to_coverage_enabled: false,
-                },
-                multiview: None,
+        let main_viewport = screen_target.viewport;
+        self.camera.set_viewport(main_viewport[2] as u32, main_viewport[3] as u32);
+
+        let (draw_calls, camera_uniform) = self.render_view(
+            &mut encoder,
+            &draws,
+            &self.camera,
+            &screen_target,
+            &mut stats,
+        );
+
+        for screen_view in self.views.iter().filter(|view| view.enabled) {
+            let ViewTarget::Screen(viewport) = screen_view.target else {
+                continue;
+            };
+
+            screen_target.clear_color = None;
+            screen_target.viewport_clear = Some(self.render_mode.clear_color(screen_view.clear_color));
+            screen_target.viewport = viewport.to_pixels(self.config.width, self.config.height);
+
+            let mut camera = screen_view.camera;
+            camera.set_viewport(screen_target.viewport[2] as u32, screen_target.viewport[3] as u32);
+
+            self.render_view(&mut encoder, &draws, &camera, &screen_target, &mut stats);
+        }
+
+        let depth_view_pipeline = self.pipelines.get(&self.depth_view.pipeline_key());
+
+        if let (RenderMode::Depth, Some(pipeline)) = (self.render_mode, depth_view_pipeline) {
+            self.depth_view.render(
+                &self.device,
+                &self.queue,
+                &mut encoder,
+                pipeline,
+                &self.depth_texture.view,
+                &self.camera,
+                main_viewport,
+                self.post.scene_view(),
+            );
+        }
+
+        // Into the HDR frame, so sprites get post-processing and the fade like the rest of the scene.
+        let sprite_projection = self.sprite_camera.matrix(self.config.width as f32, self.config.height as f32);
+        self.sprite_camera_uniform.update(CameraUniform::new(sprite_projection, cgmath::Point3::new(0.0, 0.0, 1.0)), &self.queue);
+        self.render_quads(&mut encoder, self.post.scene_view(), HDR_FORMAT, &sprites, &self.sprite_camera_uniform, "Sprite pass");
+
+        let mut pick_request = self.pick_request.take();
+
+        // The readback buffer is still mapped for an earlier pick, try again next frame.
+        if self.picking.as_ref().is_some_and(Picking::is_reading) {
+            self.pick_request = pick_request.take();
+        }
+
+        let picking_pipeline = self.pipelines.get(&Picking::pipeline_key());
+
+        if let (Some(pixel), Some(pipeline)) = (pick_request, picking_pipeline) {
+            let picking = match self.picking.take() {
+                Some(picking) if picking.is_sized_for(&self.config) => picking,
+                _ => Picking::new(&self.device, &self.config),
+            };
+
+            picking.render(
+                &mut encoder,
+                pipeline,
+                &draw_calls,
+                &self.meshes,
+                &self.textures,
+                &camera_uniform.bind_group,
+                main_viewport,
+                pixel,
+            );
+
+            self.picking = Some(picking);
+        }
+
+        self.post.render(&self.device, &self.queue, &mut encoder, &view, self.fade, self.render_mode.post_effects());
+
+        // Window pixels, unlike sprites the overlay doesn't follow the sprite camera.
+        let (width, height) = (self.config.width as f32, self.config.height as f32);
+        let overlay_projection = OPENGL_TO_WGPU_MATRIX * cgmath::ortho(0.0, width, height, 0.0, -1.0, 1.0);
+        self.overlay_camera.update(CameraUniform::new(overlay_projection, cgmath::Point3::new(0.0, 0.0, 1.0)), &self.queue);
+        self.render_quads(&mut encoder, &view, self.config.format, &overlay, &self.overlay_camera, "Overlay pass");
+
+        self.queue.submit(Some(encoder.finish()));
+
+        if let Some(picking) = &mut self.picking {
+            if pick_request.is_some() {
+                picking.start_read();
+                // Ids of the pick refer to this frame's draws, kept until the pixel is read.
+                self.pick_draws = draws;
             }
-        )
+
+            if let Some(id) = picking.poll(&self.device) {
+                self.pick_result = id.and_then(|id| {
+                    let DrawCall { params, matrix, .. } = *self.pick_draws.get(id as usize)?;
+                    Some(PickResult { id, params, matrix })
+                });
+            }
+        }
+
+        self.draw_calls = draw_calls;
+        self.stats = stats;
+
+        output.present();
+
+        Ok(())
     }
-}
\ No newline at end of file
+}
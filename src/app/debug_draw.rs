@@ -0,0 +1,302 @@
+use std::f32::consts::TAU;
+use cgmath::{EuclideanSpace, InnerSpace};
+use wgpu::util::DeviceExt;
+use crate::app::camera::Camera;
+use crate::app::geometry::Aabb;
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct LineVertex {
+    position: [f32; 3],
+    color: [f32; 4],
+}
+
+impl LineVertex {
+    pub fn new(position: cgmath::Point3<f32>, color: [f32; 4]) -> Self {
+        Self {
+            position: position.into(),
+            color,
+        }
+    }
+
+    pub fn desc() -> wgpu::VertexBufferLayout<'static> {
+        use std::mem;
+
+        wgpu::VertexBufferLayout {
+            array_stride: mem::size_of::<LineVertex>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &[
+                wgpu::VertexAttribute {
+                    offset: 0,
+                    shader_location: 0,
+                    format: wgpu::VertexFormat::Float32x3,
+                },
+                wgpu::VertexAttribute {
+                    offset: mem::size_of::<[f32; 3]>() as wgpu::BufferAddress,
+                    shader_location: 1,
+                    format: wgpu::VertexFormat::Float32x4,
+                },
+            ],
+        }
+    }
+}
+
+/// Immediate-mode lines for debugging, queued every frame and drawn over the scene.
+/// Calls are ignored while debug drawing is disabled, so they can stay in game code.
+pub struct DebugDraw {
+    enabled: bool,
+    vertices: Vec<LineVertex>,
+    /// Camera right and up vectors, `text_3d` is drawn facing the camera.
+    billboard: (cgmath::Vector3<f32>, cgmath::Vector3<f32>),
+}
+
+impl DebugDraw {
+    const CIRCLE_SEGMENTS: usize = 24;
+
+    pub fn new(enabled: bool) -> Self {
+        Self {
+            enabled,
+            vertices: Vec::new(),
+            billboard: (cgmath::Vector3::unit_x(), cgmath::Vector3::unit_y()),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Disabling also drops the lines queued this frame.
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+
+        if !enabled {
+            self.vertices.clear();
+        }
+    }
+
+    pub fn vertices(&self) -> &[LineVertex] {
+        &self.vertices
+    }
+
+    pub fn clear(&mut self) {
+        self.vertices.clear();
+    }
+
+    pub(crate) fn face_camera(&mut self, camera: &Camera) {
+        let forward = (camera.target - camera.eye).normalize();
+        let right = forward.cross(camera.up).normalize();
+
+        if right.x.is_finite() {
+            self.billboard = (right, right.cross(forward));
+        }
+    }
+
+    pub fn line(&mut self, a: cgmath::Point3<f32>, b: cgmath::Point3<f32>, color: [f32; 4]) {
+        if !self.enabled {
+            return;
+        }
+
+        self.vertices.push(LineVertex::new(a, color));
+        self.vertices.push(LineVertex::new(b, color));
+    }
+
+    pub fn aabb(&mut self, aabb: &Aabb, color: [f32; 4]) {
+        let corners = aabb.corners();
+
+        // Corners are indexed by bits: 1 is max x, 2 is max y and 4 is max z,
+        // so every edge joins two corners that differ in a single bit.
+        for bit in [1, 2, 4] {
+            for index in (0..8).filter(|index| index & bit == 0) {
+                self.line(corners[index], corners[index | bit], color);
+            }
+        }
+    }
+
+    /// Three circles around the main axes.
+    pub fn sphere(&mut self, center: cgmath::Point3<f32>, radius: f32, color: [f32; 4]) {
+        let (x, y, z) = (cgmath::Vector3::unit_x(), cgmath::Vector3::unit_y(), cgmath::Vector3::unit_z());
+
+        self.circle(center, x * radius, y * radius, color);
+        self.circle(center, y * radius, z * radius, color);
+        self.circle(center, z * radius, x * radius, color);
+    }
+
+    /// Lines on the XZ plane around `center`, `cells` in each direction, e.g. the tile grid.
+    pub fn grid(&mut self, center: cgmath::Point3<f32>, cells: u32, cell_size: f32, color: [f32; 4]) {
+        let extent = cells as f32 * cell_size;
+
+        for i in 0..=cells * 2 {
+            let offset = i as f32 * cell_size - extent;
+
+            self.line(
+                center + cgmath::Vector3::new(offset, 0.0, -extent),
+                center + cgmath::Vector3::new(offset, 0.0, extent),
+                color,
+            );
+
+            self.line(
+                center + cgmath::Vector3::new(-extent, 0.0, offset),
+                center + cgmath::Vector3::new(extent, 0.0, offset),
+                color,
+            );
+        }
+    }
+
+    /// X, Y and Z axes of `matrix` in red, green and blue.
+    pub fn axes(&mut self, matrix: &cgmath::Matrix4<f32>, length: f32) {
+        let origin = cgmath::Point3::from_vec(matrix.w.truncate());
+
+        self.line(origin, origin + matrix.x.truncate() * length, [1.0, 0.0, 0.0, 1.0]);
+        self.line(origin, origin + matrix.y.truncate() * length, [0.0, 1.0, 0.0, 1.0]);
+        self.line(origin, origin + matrix.z.truncate() * length, [0.0, 0.0, 1.0, 1.0]);
+    }
+
+    /// Draws `text` with a stroke font, centered above `position` and facing the camera.
+    /// Only digits, latin letters and common punctuation are supported, letters are drawn uppercase.
+    pub fn text_3d(&mut self, position: cgmath::Point3<f32>, text: &str, height: f32, color: [f32; 4]) {
+        if !self.enabled {
+            return;
+        }
+
+        let (right, up) = self.billboard;
+        let scale = height / stroke_font::HEIGHT;
+
+        for (row, line) in text.lines().enumerate() {
+            let width = line.chars().count() as f32 * stroke_font::ADVANCE - stroke_font::SPACING;
+            let baseline = -(row as f32) * stroke_font::LINE_HEIGHT;
+
+            for (column, character) in line.chars().enumerate() {
+                let left = column as f32 * stroke_font::ADVANCE - width / 2.0;
+
+                let point = |(x, y): (f32, f32)| {
+                    position + right * ((left + x) * scale) + up * ((baseline + y) * scale)
+                };
+
+                for stroke in stroke_font::glyph(character).split_whitespace() {
+                    let points: Vec<_> = stroke_font::points(stroke).collect();
+
+                    for segment in points.windows(2) {
+                        self.line(point(segment[0]), point(segment[1]), color);
+                    }
+                }
+            }
+        }
+    }
+
+    fn circle(
+        &mut self,
+        center: cgmath::Point3<f32>,
+        u: cgmath::Vector3<f32>,
+        v: cgmath::Vector3<f32>,
+        color: [f32; 4],
+    ) {
+        let point = |i: usize| {
+            let angle = i as f32 / Self::CIRCLE_SEGMENTS as f32 * TAU;
+            center + u * angle.cos() + v * angle.sin()
+        };
+
+        for i in 0..Self::CIRCLE_SEGMENTS {
+            self.line(point(i), point(i + 1), color);
+        }
+    }
+}
+
+/// Line vertices uploaded for one frame.
+pub struct LineBuffer {
+    buffer: wgpu::Buffer,
+    len: u32,
+}
+
+impl LineBuffer {
+    pub fn new(device: &wgpu::Device, vertices: &[LineVertex]) -> Option<Self> {
+        if vertices.is_empty() {
+            return None;
+        }
+
+        let buffer = device.create_buffer_init(
+            &wgpu::util::BufferInitDescriptor {
+                label: Some("Debug line buffer"),
+                contents: bytemuck::cast_slice(vertices),
+                usage: wgpu::BufferUsages::VERTEX,
+            }
+        );
+
+        Some(Self {
+            buffer,
+            len: vertices.len() as u32,
+        })
+    }
+
+    pub fn draw<'a>(&'a self, render_pass: &mut wgpu::RenderPass<'a>) {
+        render_pass.set_vertex_buffer(0, self.buffer.slice(..));
+        render_pass.draw(0..self.len, 0..1);
+    }
+}
+
+/// Glyphs as polylines on a 4 by 6 grid with y pointing up. Strokes are separated by spaces
+/// and every point is two digits, so "060040" is an L.
+mod stroke_font {
+    pub const HEIGHT: f32 = 6.0;
+    pub const SPACING: f32 = 2.0;
+    pub const ADVANCE: f32 = 4.0 + SPACING;
+    pub const LINE_HEIGHT: f32 = HEIGHT + 3.0;
+
+    pub fn glyph(character: char) -> &'static str {
+        match character.to_ascii_uppercase() {
+            '0' => "0040460600 0046",
+            '1' => "152620 1030",
+            '2' => "064643030040",
+            '3' => "06464000 0343",
+            '4' => "060343 4640",
+            '5' | 'S' => "460603434000",
+            '6' => "460600404303",
+            '7' => "064610",
+            '8' => "0040460600 0343",
+            '9' => "430306464000",
+            'A' => "0004264440 0343",
+            'B' => "00063645443303 3342413000",
+            'C' => "46060040",
+            'D' => "00062644422000",
+            'E' => "46060040 0333",
+            'F' => "460600 0333",
+            'G' => "460600404323",
+            'H' => "0600 4640 0343",
+            'I' => "1636 2620 1030",
+            'J' => "46400002",
+            'K' => "0600 460340",
+            'L' => "060040",
+            'M' => "0006234640",
+            'N' => "00064046",
+            'O' => "0040460600",
+            'P' => "0006464303",
+            'Q' => "0040460600 2240",
+            'R' => "0006464303 2340",
+            'T' => "0646 2620",
+            'U' => "06004046",
+            'V' => "062046",
+            'W' => "0610233046",
+            'X' => "0046 0640",
+            'Y' => "062346 2320",
+            'Z' => "06460040",
+            '-' => "0343",
+            '+' => "0343 2125",
+            '=' => "0242 0444",
+            '_' => "0040",
+            '.' => "2021",
+            ',' => "2110",
+            ':' => "2122 2425",
+            '/' => "0046",
+            '(' => "36242230",
+            ')' => "16242210",
+            '!' => "2622 2021",
+            '?' => "05163645442322 2120",
+            _ => "",
+        }
+    }
+
+    pub fn points(stroke: &str) -> impl Iterator<Item = (f32, f32)> + '_ {
+        stroke.as_bytes()
+            .chunks_exact(2)
+            .map(|point| ((point[0] - b'0') as f32, (point[1] - b'0') as f32))
+    }
+}
@@ -0,0 +1,40 @@
+// Draws the main depth buffer in grayscale for `RenderMode::Depth`.
+
+#ifdef MULTISAMPLED
+@group(0) @binding(0)
+var t_depth: texture_depth_multisampled_2d;
+#else
+@group(0) @binding(0)
+var t_depth: texture_depth_2d;
+#endif
+
+// Near plane, far plane and whether the projection is orthographic.
+@group(1) @binding(0)
+var<uniform> params: vec4<f32>;
+
+// One triangle covering the whole viewport, no vertex buffer needed.
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> @builtin(position) vec4<f32> {
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+
+    return vec4<f32>(uv * vec2<f32>(2.0, -2.0) + vec2<f32>(-1.0, 1.0), 0.0, 1.0);
+}
+
+@fragment
+fn fs_main(@builtin(position) position: vec4<f32>) -> @location(0) vec4<f32> {
+    // Level for a regular texture, sample index for a multisampled one.
+    let depth = textureLoad(t_depth, vec2<i32>(position.xy), 0);
+
+    let near = params.x;
+    let far = params.y;
+
+    // Perspective depth is hyperbolic, orthographic depth is already linear.
+    var distance = near + depth * (far - near);
+    if params.z < 0.5 {
+        distance = near * far / (far - depth * (far - near));
+    }
+
+    let brightness = 1.0 - clamp((distance - near) / (far - near), 0.0, 1.0);
+
+    return vec4<f32>(vec3<f32>(brightness * brightness), 1.0);
+}
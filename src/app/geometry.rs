@@ -0,0 +1,400 @@
+use cgmath::{EuclideanSpace, InnerSpace, Matrix, SquareMatrix, Transform};
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Aabb {
+    pub min: cgmath::Point3<f32>,
+    pub max: cgmath::Point3<f32>,
+}
+
+impl Aabb {
+    pub fn new(min: cgmath::Point3<f32>, max: cgmath::Point3<f32>) -> Self {
+        Self { min, max }
+    }
+
+    pub fn from_points(points: impl IntoIterator<Item = cgmath::Point3<f32>>) -> Option<Self> {
+        let mut points = points.into_iter();
+        let first = points.next()?;
+
+        Some(points.fold(Self::new(first, first), |aabb, point| Self {
+            min: cgmath::Point3::new(
+                aabb.min.x.min(point.x),
+                aabb.min.y.min(point.y),
+                aabb.min.z.min(point.z),
+            ),
+            max: cgmath::Point3::new(
+                aabb.max.x.max(point.x),
+                aabb.max.y.max(point.y),
+                aabb.max.z.max(point.z),
+            ),
+        }))
+    }
+
+    pub fn center(&self) -> cgmath::Point3<f32> {
+        self.min.midpoint(self.max)
+    }
+
+    pub fn corners(&self) -> [cgmath::Point3<f32>; 8] {
+        let (min, max) = (self.min, self.max);
+
+        [
+            cgmath::Point3::new(min.x, min.y, min.z),
+            cgmath::Point3::new(max.x, min.y, min.z),
+            cgmath::Point3::new(min.x, max.y, min.z),
+            cgmath::Point3::new(max.x, max.y, min.z),
+            cgmath::Point3::new(min.x, min.y, max.z),
+            cgmath::Point3::new(max.x, min.y, max.z),
+            cgmath::Point3::new(min.x, max.y, max.z),
+            cgmath::Point3::new(max.x, max.y, max.z),
+        ]
+    }
+
+    /// Axis-aligned box enclosing this box after `matrix` is applied.
+    pub fn transform(&self, matrix: &cgmath::Matrix4<f32>) -> Self {
+        Self::from_points(self.corners().map(|corner| matrix.transform_point(corner)))
+            .unwrap()
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Ray {
+    pub origin: cgmath::Point3<f32>,
+    pub direction: cgmath::Vector3<f32>,
+}
+
+impl Ray {
+    pub fn new(origin: cgmath::Point3<f32>, direction: cgmath::Vector3<f32>) -> Self {
+        Self { origin, direction }
+    }
+
+    /// Ray from the camera through `cursor` (in pixels) using the inverse view-projection matrix.
+    pub fn from_screen(
+        cursor: cgmath::Point2<f32>,
+        viewport: cgmath::Vector2<f32>,
+        view_projection: &cgmath::Matrix4<f32>,
+    ) -> Option<Self> {
+        let inverse = view_projection.invert()?;
+
+        let x = 2.0 * cursor.x / viewport.x - 1.0;
+        let y = 1.0 - 2.0 * cursor.y / viewport.y;
+
+        // wgpu clip space depth goes from 0 at the near plane to 1 at the far plane.
+        let near = inverse.transform_point(cgmath::Point3::new(x, y, 0.0));
+        let far = inverse.transform_point(cgmath::Point3::new(x, y, 1.0));
+
+        Some(Self::new(near, (far - near).normalize()))
+    }
+
+    pub fn at(&self, distance: f32) -> cgmath::Point3<f32> {
+        self.origin + self.direction * distance
+    }
+
+    /// Distance along the ray to the plane through `point` with `normal`.
+    pub fn intersect_plane(&self, point: cgmath::Point3<f32>, normal: cgmath::Vector3<f32>) -> Option<f32> {
+        let denominator = normal.dot(self.direction);
+
+        if denominator.abs() < f32::EPSILON {
+            return None;
+        }
+
+        let distance = normal.dot(point - self.origin) / denominator;
+        (distance >= 0.0).then_some(distance)
+    }
+
+    /// Point where the ray hits the horizontal plane at `height`.
+    pub fn intersect_ground(&self, height: f32) -> Option<cgmath::Point3<f32>> {
+        let point = cgmath::Point3::new(0.0, height, 0.0);
+
+        self.intersect_plane(point, cgmath::Vector3::unit_y())
+            .map(|distance| self.at(distance))
+    }
+
+    /// Slab test, returns the distance to the entry point or 0 when starting inside.
+    pub fn intersect_aabb(&self, aabb: &Aabb) -> Option<f32> {
+        let mut near = 0.0f32;
+        let mut far = f32::INFINITY;
+
+        for axis in 0..3 {
+            let origin = self.origin[axis];
+            let direction = self.direction[axis];
+            let (min, max) = (aabb.min[axis], aabb.max[axis]);
+
+            if direction.abs() < f32::EPSILON {
+                if origin < min || origin > max {
+                    return None;
+                }
+
+                continue;
+            }
+
+            let first = (min - origin) / direction;
+            let second = (max - origin) / direction;
+
+            near = near.max(first.min(second));
+            far = far.min(first.max(second));
+
+            if near > far {
+                return None;
+            }
+        }
+
+        Some(near)
+    }
+
+    /// Intersects a box given in the local space of `matrix`, so rotated instances are exact.
+    pub fn intersect_transformed_aabb(&self, aabb: &Aabb, matrix: &cgmath::Matrix4<f32>) -> Option<f32> {
+        let inverse = matrix.invert()?;
+
+        let local = Ray::new(
+            inverse.transform_point(self.origin),
+            inverse.transform_vector(self.direction),
+        );
+
+        // The local direction isn't renormalized, so distances stay in world units.
+        local.intersect_aabb(aabb)
+    }
+}
+
+/// Six clip planes extracted from a view-projection matrix, normals pointing inside.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Frustum {
+    planes: [cgmath::Vector4<f32>; 6],
+}
+
+impl Frustum {
+    /// Gribb-Hartmann extraction for wgpu clip space, where depth goes from 0 to 1.
+    pub fn from_matrix(view_projection: &cgmath::Matrix4<f32>) -> Self {
+        let row = |index: usize| view_projection.row(index);
+        let (x, y, z, w) = (row(0), row(1), row(2), row(3));
+
+        let planes = [w + x, w - x, w + y, w - y, z, w - z].map(|plane| {
+            let length = plane.truncate().magnitude();
+            plane / length
+        });
+
+        Self { planes }
+    }
+
+    pub fn intersects_aabb(&self, aabb: &Aabb) -> bool {
+        self.planes.iter().all(|plane| {
+            // Corner furthest along the plane normal.
+            let corner = cgmath::Vector4::new(
+                if plane.x >= 0.0 { aabb.max.x } else { aabb.min.x },
+                if plane.y >= 0.0 { aabb.max.y } else { aabb.min.y },
+                if plane.z >= 0.0 { aabb.max.z } else { aabb.min.z },
+                1.0,
+            );
+
+            plane.dot(corner) >= 0.0
+        })
+    }
+}
+
+/// Axis aligned rectangle in pixels, with y pointing down from the top left corner of the window.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct Rect {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+}
+
+impl Rect {
+    pub const fn new(x: f32, y: f32, width: f32, height: f32) -> Self {
+        Self { x, y, width, height }
+    }
+
+    pub fn right(&self) -> f32 {
+        self.x + self.width
+    }
+
+    pub fn bottom(&self) -> f32 {
+        self.y + self.height
+    }
+
+    pub fn center(&self) -> cgmath::Point2<f32> {
+        cgmath::Point2::new(self.x + self.width / 2.0, self.y + self.height / 2.0)
+    }
+
+    pub fn contains(&self, point: cgmath::Point2<f32>) -> bool {
+        point.x >= self.x && point.x < self.right() && point.y >= self.y && point.y < self.bottom()
+    }
+
+    /// Overlapping part of both rectangles, `None` when they don't overlap.
+    pub fn intersection(&self, other: &Rect) -> Option<Rect> {
+        let (x, y) = (self.x.max(other.x), self.y.max(other.y));
+        let (right, bottom) = (self.right().min(other.right()), self.bottom().min(other.bottom()));
+
+        (right > x && bottom > y).then(|| Rect::new(x, y, right - x, bottom - y))
+    }
+
+    /// Shrinks the rectangle by `amount` on every side.
+    pub fn inset(&self, amount: f32) -> Rect {
+        Rect::new(
+            self.x + amount,
+            self.y + amount,
+            (self.width - amount * 2.0).max(0.0),
+            (self.height - amount * 2.0).max(0.0),
+        )
+    }
+
+    pub fn translate(&self, x: f32, y: f32) -> Rect {
+        Rect::new(self.x + x, self.y + y, self.width, self.height)
+    }
+
+    /// Splits off the top `height` pixels, the rest starts `spacing` pixels below them.
+    pub fn split_top(&self, height: f32, spacing: f32) -> (Rect, Rect) {
+        let height = height.min(self.height);
+        let rest = (self.height - height - spacing).max(0.0);
+
+        (
+            Rect::new(self.x, self.y, self.width, height),
+            Rect::new(self.x, self.bottom() - rest, self.width, rest),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EPSILON: f32 = 1e-4;
+
+    fn assert_near(a: f32, b: f32) {
+        assert!((a - b).abs() < EPSILON, "{a} != {b}");
+    }
+
+    fn unit_box() -> Aabb {
+        Aabb::new(cgmath::Point3::new(-1.0, -1.0, -1.0), cgmath::Point3::new(1.0, 1.0, 1.0))
+    }
+
+    #[test]
+    fn from_screen_maps_pixels_to_clip_space() {
+        let viewport = cgmath::Vector2::new(800.0, 600.0);
+        let identity = cgmath::Matrix4::identity();
+
+        let center = Ray::from_screen(cgmath::Point2::new(400.0, 300.0), viewport, &identity).unwrap();
+        assert_eq!(center.origin, cgmath::Point3::new(0.0, 0.0, 0.0));
+        assert_eq!(center.direction, cgmath::Vector3::unit_z());
+
+        // y points down in pixels and up in clip space.
+        let corner = Ray::from_screen(cgmath::Point2::new(0.0, 0.0), viewport, &identity).unwrap();
+        assert_eq!(corner.origin, cgmath::Point3::new(-1.0, 1.0, 0.0));
+    }
+
+    #[test]
+    fn from_screen_follows_the_camera() {
+        let eye = cgmath::Point3::new(0.0, 10.0, 10.0);
+        let view = cgmath::Matrix4::look_at_rh(eye, cgmath::Point3::new(0.0, 0.0, 0.0), cgmath::Vector3::unit_y());
+        let projection = cgmath::perspective(cgmath::Deg(45.0), 4.0 / 3.0, 0.1, 100.0);
+        let view_projection = projection * view;
+
+        let viewport = cgmath::Vector2::new(800.0, 600.0);
+        let ray = Ray::from_screen(cgmath::Point2::new(400.0, 300.0), viewport, &view_projection).unwrap();
+
+        let ground = ray.intersect_ground(0.0).unwrap();
+        assert_near(ground.x, 0.0);
+        assert_near(ground.z, 0.0);
+
+        let singular = cgmath::Matrix4::from_scale(0.0);
+        assert!(Ray::from_screen(cgmath::Point2::new(0.0, 0.0), viewport, &singular).is_none());
+    }
+
+    #[test]
+    fn intersect_plane() {
+        let ray = Ray::new(cgmath::Point3::new(0.0, 5.0, 0.0), -cgmath::Vector3::unit_y());
+
+        assert_eq!(ray.intersect_plane(cgmath::Point3::new(3.0, 1.0, 3.0), cgmath::Vector3::unit_y()), Some(4.0));
+        assert_eq!(ray.intersect_ground(0.0), Some(cgmath::Point3::new(0.0, 0.0, 0.0)));
+
+        // Behind the origin and parallel to the plane.
+        assert_eq!(ray.intersect_ground(6.0), None);
+        assert_eq!(ray.intersect_plane(cgmath::Point3::new(0.0, 0.0, 0.0), cgmath::Vector3::unit_x()), None);
+    }
+
+    #[test]
+    fn intersect_aabb() {
+        let aabb = unit_box();
+
+        let hit = Ray::new(cgmath::Point3::new(-5.0, 0.0, 0.0), cgmath::Vector3::unit_x());
+        assert_eq!(hit.intersect_aabb(&aabb), Some(4.0));
+
+        let inside = Ray::new(cgmath::Point3::new(0.0, 0.0, 0.0), cgmath::Vector3::unit_x());
+        assert_eq!(inside.intersect_aabb(&aabb), Some(0.0));
+
+        let away = Ray::new(cgmath::Point3::new(-5.0, 0.0, 0.0), -cgmath::Vector3::unit_x());
+        assert_eq!(away.intersect_aabb(&aabb), None);
+
+        let beside = Ray::new(cgmath::Point3::new(-5.0, 2.0, 0.0), cgmath::Vector3::unit_x());
+        assert_eq!(beside.intersect_aabb(&aabb), None);
+
+        let diagonal = Ray::new(cgmath::Point3::new(-3.0, -3.0, 0.0), cgmath::Vector3::new(1.0, 1.0, 0.0).normalize());
+        assert_near(diagonal.intersect_aabb(&aabb).unwrap(), 2.0 * 2.0f32.sqrt());
+    }
+
+    #[test]
+    fn intersect_transformed_aabb() {
+        let matrix = cgmath::Matrix4::from_translation(cgmath::Vector3::new(10.0, 0.0, 0.0))
+            * cgmath::Matrix4::from_scale(2.0);
+        let ray = Ray::new(cgmath::Point3::new(0.0, 0.0, 0.0), cgmath::Vector3::unit_x());
+
+        assert_near(ray.intersect_transformed_aabb(&unit_box(), &matrix).unwrap(), 8.0);
+        assert_eq!(unit_box().transform(&matrix).min, cgmath::Point3::new(8.0, -2.0, -2.0));
+    }
+
+    #[test]
+    fn frustum_culls_boxes_outside() {
+        let view = cgmath::Matrix4::look_at_rh(
+            cgmath::Point3::new(0.0, 0.0, 10.0),
+            cgmath::Point3::new(0.0, 0.0, 0.0),
+            cgmath::Vector3::unit_y(),
+        );
+        let projection = cgmath::perspective(cgmath::Deg(90.0), 1.0, 1.0, 50.0);
+        // Maps OpenGL depth from -1..1 to the 0..1 of wgpu clip space.
+        let to_wgpu_depth = cgmath::Matrix4::from_translation(cgmath::Vector3::new(0.0, 0.0, 0.5))
+            * cgmath::Matrix4::from_nonuniform_scale(1.0, 1.0, 0.5);
+        let frustum = Frustum::from_matrix(&(to_wgpu_depth * projection * view));
+
+        let at = |x: f32, y: f32, z: f32| Aabb::new(
+            cgmath::Point3::new(x - 0.5, y - 0.5, z - 0.5),
+            cgmath::Point3::new(x + 0.5, y + 0.5, z + 0.5),
+        );
+
+        assert!(frustum.intersects_aabb(&at(0.0, 0.0, 0.0)));
+        assert!(frustum.intersects_aabb(&at(5.0, 0.0, 0.0)));
+
+        // Behind the camera, past the far plane and beside the 90 degree cone.
+        assert!(!frustum.intersects_aabb(&at(0.0, 0.0, 20.0)));
+        assert!(!frustum.intersects_aabb(&at(0.0, 0.0, -50.0)));
+        assert!(!frustum.intersects_aabb(&at(15.0, 0.0, 0.0)));
+        assert!(!frustum.intersects_aabb(&at(0.0, -15.0, 0.0)));
+
+        // Crossing a plane counts as visible.
+        assert!(frustum.intersects_aabb(&at(10.3, 0.0, 0.0)));
+    }
+
+    #[test]
+    fn rect_intersection() {
+        let rect = Rect::new(0.0, 0.0, 100.0, 50.0);
+
+        assert_eq!(rect.intersection(&Rect::new(80.0, 40.0, 50.0, 50.0)), Some(Rect::new(80.0, 40.0, 20.0, 10.0)));
+        assert_eq!(rect.intersection(&Rect::new(10.0, 10.0, 5.0, 5.0)), Some(Rect::new(10.0, 10.0, 5.0, 5.0)));
+
+        // Touching edges don't overlap.
+        assert_eq!(rect.intersection(&Rect::new(100.0, 0.0, 10.0, 10.0)), None);
+        assert_eq!(rect.intersection(&Rect::new(0.0, 60.0, 10.0, 10.0)), None);
+    }
+
+    #[test]
+    fn rect_split_top() {
+        let rect = Rect::new(10.0, 20.0, 100.0, 50.0);
+
+        let (top, rest) = rect.split_top(20.0, 5.0);
+        assert_eq!(top, Rect::new(10.0, 20.0, 100.0, 20.0));
+        assert_eq!(rest, Rect::new(10.0, 45.0, 100.0, 25.0));
+
+        // Taller than the rectangle, nothing is left below.
+        let (top, rest) = rect.split_top(80.0, 5.0);
+        assert_eq!(top, rect);
+        assert_eq!(rest, Rect::new(10.0, 70.0, 100.0, 0.0));
+    }
+}
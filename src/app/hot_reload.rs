@@ -0,0 +1,86 @@
+use std::collections::HashMap;
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::task::{Poll, Waker};
+use std::time::{Duration, Instant, SystemTime};
+
+/// Polls the modification time of a few files, cheap enough to call every frame.
+pub struct FileWatcher {
+    files: HashMap<PathBuf, Option<SystemTime>>,
+    interval: Duration,
+    last_poll: Instant,
+}
+
+impl FileWatcher {
+    pub fn new(interval: Duration) -> Self {
+        Self {
+            files: HashMap::new(),
+            interval,
+            last_poll: Instant::now(),
+        }
+    }
+
+    pub fn watch(&mut self, path: impl Into<PathBuf>) {
+        let path = path.into();
+        let modified = Self::modified(&path);
+        self.files.insert(path, modified);
+    }
+
+    pub fn unwatch(&mut self, path: &Path) {
+        self.files.remove(path);
+    }
+
+    /// Files modified since the previous poll, checked at most once per interval.
+    pub fn poll(&mut self) -> Vec<PathBuf> {
+        if self.last_poll.elapsed() < self.interval {
+            return vec![];
+        }
+
+        self.last_poll = Instant::now();
+
+        self.files.iter_mut()
+            .filter_map(|(path, last_modified)| {
+                let modified = Self::modified(path);
+
+                // Editors often delete and recreate the file, wait until it's back.
+                if modified.is_none() || modified == *last_modified {
+                    return None;
+                }
+
+                *last_modified = modified;
+                Some(path.clone())
+            })
+            .collect()
+    }
+
+    fn modified(path: &Path) -> Option<SystemTime> {
+        std::fs::metadata(path).and_then(|metadata| metadata.modified()).ok()
+    }
+}
+
+/// Runs `create` inside a validation error scope, so broken shaders and pipelines are
+/// returned as errors instead of hitting the uncaptured error handler, which panics.
+pub fn validated<T>(device: &wgpu::Device, create: impl FnOnce() -> T) -> Result<T, wgpu::Error> {
+    device.push_error_scope(wgpu::ErrorFilter::Validation);
+    let value = create();
+
+    match block_on(device.pop_error_scope()) {
+        Some(error) => Err(error),
+        None => Ok(value),
+    }
+}
+
+/// Native backends resolve error scopes right away, so this doesn't actually spin.
+/// The tokio runtime can't be used here since rendering already runs inside it.
+fn block_on<F: Future>(future: F) -> F::Output {
+    let mut future = std::pin::pin!(future);
+    let mut context = std::task::Context::from_waker(Waker::noop());
+
+    loop {
+        if let Poll::Ready(value) = future.as_mut().poll(&mut context) {
+            return value;
+        }
+
+        std::thread::yield_now();
+    }
+}
@@ -0,0 +1,132 @@
+use std::collections::HashSet;
+use winit::dpi::PhysicalSize;
+use winit::event::{DeviceEvent, ElementState, KeyEvent, MouseButton, MouseScrollDelta, WindowEvent};
+use winit::keyboard::{KeyCode, PhysicalKey};
+
+/// Held keys and mouse state, updated by `App` from window and device events.
+pub struct Input {
+    keys: HashSet<KeyCode>,
+    /// Keys pressed since the previous frame, including key repeats.
+    pressed_keys: HashSet<KeyCode>,
+    mouse_buttons: HashSet<MouseButton>,
+    pressed_mouse_buttons: HashSet<MouseButton>,
+    cursor: Option<cgmath::Point2<f32>>,
+    mouse_delta: cgmath::Vector2<f32>,
+    scroll: f32,
+    window_size: PhysicalSize<u32>,
+}
+
+impl Input {
+    pub fn new(window_size: PhysicalSize<u32>) -> Self {
+        Self {
+            keys: HashSet::new(),
+            pressed_keys: HashSet::new(),
+            mouse_buttons: HashSet::new(),
+            pressed_mouse_buttons: HashSet::new(),
+            cursor: None,
+            mouse_delta: cgmath::Vector2::new(0.0, 0.0),
+            scroll: 0.0,
+            window_size,
+        }
+    }
+
+    pub fn is_key_down(&self, key: KeyCode) -> bool {
+        self.keys.contains(&key)
+    }
+
+    /// Pressed since the previous frame, held keys repeat at the OS rate.
+    pub fn is_key_pressed(&self, key: KeyCode) -> bool {
+        self.pressed_keys.contains(&key)
+    }
+
+    pub fn is_mouse_down(&self, button: MouseButton) -> bool {
+        self.mouse_buttons.contains(&button)
+    }
+
+    pub fn is_mouse_pressed(&self, button: MouseButton) -> bool {
+        self.pressed_mouse_buttons.contains(&button)
+    }
+
+    /// Cursor position in physical pixels, `None` while the cursor is outside the window.
+    pub fn cursor(&self) -> Option<cgmath::Point2<f32>> {
+        self.cursor
+    }
+
+    /// Raw mouse movement since the previous frame.
+    pub fn mouse_delta(&self) -> cgmath::Vector2<f32> {
+        self.mouse_delta
+    }
+
+    /// Scroll wheel lines since the previous frame, positive when scrolling up.
+    pub fn scroll(&self) -> f32 {
+        self.scroll
+    }
+
+    pub fn window_size(&self) -> PhysicalSize<u32> {
+        self.window_size
+    }
+
+    pub fn handle_window_event(&mut self, event: &WindowEvent) {
+        match event {
+            WindowEvent::KeyboardInput {
+                event: KeyEvent {
+                    physical_key: PhysicalKey::Code(code),
+                    state,
+                    ..
+                },
+                ..
+            } => match state {
+                ElementState::Pressed => {
+                    self.keys.insert(*code);
+                    self.pressed_keys.insert(*code);
+                },
+                ElementState::Released => { self.keys.remove(code); },
+            },
+
+            WindowEvent::MouseInput { state, button, .. } => match state {
+                ElementState::Pressed => {
+                    self.mouse_buttons.insert(*button);
+                    self.pressed_mouse_buttons.insert(*button);
+                },
+                ElementState::Released => { self.mouse_buttons.remove(button); },
+            },
+
+            WindowEvent::CursorMoved { position, .. } => {
+                self.cursor = Some(cgmath::Point2::new(position.x as f32, position.y as f32));
+            },
+
+            WindowEvent::CursorLeft { .. } => self.cursor = None,
+
+            WindowEvent::MouseWheel { delta, .. } => {
+                self.scroll += match delta {
+                    MouseScrollDelta::LineDelta(_, y) => *y,
+                    MouseScrollDelta::PixelDelta(position) => position.y as f32 / 20.0,
+                };
+            },
+
+            WindowEvent::Resized(size) => self.window_size = *size,
+
+            // Releases are lost while unfocused, so nothing should stay held.
+            WindowEvent::Focused(false) => {
+                self.keys.clear();
+                self.mouse_buttons.clear();
+            },
+
+            _ => {},
+        }
+    }
+
+    pub fn handle_device_event(&mut self, event: &DeviceEvent) {
+        if let DeviceEvent::MouseMotion { delta: (x, y) } = event {
+            self.mouse_delta += cgmath::Vector2::new(*x as f32, *y as f32);
+        }
+    }
+
+    /// Clears the per-frame deltas after the frame has been rendered.
+    pub fn end_frame(&mut self) {
+        self.mouse_delta = cgmath::Vector2::new(0.0, 0.0);
+        self.scroll = 0.0;
+        self.pressed_keys.clear();
+        self.pressed_mouse_buttons.clear();
+    }
+}
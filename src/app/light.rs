@@ -0,0 +1,185 @@
+use cgmath::InnerSpace;
+use crate::app::camera::OPENGL_TO_WGPU_MATRIX;
+
+/// Point lights beyond this count are dropped, furthest from the camera first.
+pub const MAX_POINT_LIGHTS: usize = 16;
+
+/// Light coming from infinitely far away, the sun or the moon.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct DirectionalLight {
+    /// Direction the light travels in, doesn't have to be normalized.
+    pub direction: cgmath::Vector3<f32>,
+    pub color: [f32; 3],
+    pub intensity: f32,
+}
+
+impl DirectionalLight {
+    pub fn new(direction: cgmath::Vector3<f32>, color: [f32; 3], intensity: f32) -> Self {
+        Self { direction, color, intensity }
+    }
+}
+
+/// Local light such as a lantern or a lit window, fading out to nothing at `radius`.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct PointLight {
+    pub position: cgmath::Point3<f32>,
+    pub color: [f32; 3],
+    pub intensity: f32,
+    pub radius: f32,
+}
+
+impl PointLight {
+    pub fn new(position: cgmath::Point3<f32>, color: [f32; 3], intensity: f32, radius: f32) -> Self {
+        Self { position, color, intensity, radius }
+    }
+}
+
+/// Shadows cast by the sun inside a box that follows the main camera target.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct SunShadows {
+    pub enabled: bool,
+    /// Half the width of the shadowed area in world units, smaller gives sharper shadows.
+    pub extent: f32,
+    /// Length of the box along the sun direction, casters outside of it are ignored.
+    pub depth: f32,
+    /// Depth offset against shadow acne, in shadow map depth units.
+    pub bias: f32,
+}
+
+impl Default for SunShadows {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            extent: 25.0,
+            depth: 100.0,
+            bias: 0.002,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Lights {
+    /// Light added everywhere so faces turned away from every light aren't pitch black.
+    pub ambient: [f32; 3],
+    pub sun: DirectionalLight,
+    pub points: Vec<PointLight>,
+    pub shadows: SunShadows,
+}
+
+impl Default for Lights {
+    fn default() -> Self {
+        Self {
+            ambient: [0.3, 0.3, 0.3],
+            sun: DirectionalLight::new(
+                cgmath::Vector3::new(-0.4, -1.0, -0.6),
+                [1.0, 1.0, 1.0],
+                0.8,
+            ),
+            points: vec![],
+            shadows: SunShadows::default(),
+        }
+    }
+}
+
+impl Lights {
+    /// Orthographic view-projection of the sun centered on `focus`, for a `resolution` sized shadow map.
+    pub fn shadow_matrix(&self, focus: cgmath::Point3<f32>, resolution: u32) -> cgmath::Matrix4<f32> {
+        let direction = self.sun.direction.normalize();
+
+        let up = if direction.y.abs() > 0.99 {
+            cgmath::Vector3::unit_z()
+        } else {
+            cgmath::Vector3::unit_y()
+        };
+
+        let eye = focus - direction * (self.shadows.depth / 2.0);
+        let mut view = cgmath::Matrix4::look_at_rh(eye, focus, up);
+
+        // Move in whole shadow map texels so shadow edges don't crawl while the camera pans.
+        let texel = 2.0 * self.shadows.extent / resolution as f32;
+        view.w.x = (view.w.x / texel).round() * texel;
+        view.w.y = (view.w.y / texel).round() * texel;
+
+        let extent = self.shadows.extent;
+        let projection = cgmath::ortho(-extent, extent, -extent, extent, 0.0, self.shadows.depth);
+
+        OPENGL_TO_WGPU_MATRIX * projection * view
+    }
+
+    /// Packs the lights for the shader, keeping the point lights closest to `eye`.
+    pub fn to_raw(
+        &self,
+        eye: cgmath::Point3<f32>,
+        shadow_matrix: cgmath::Matrix4<f32>,
+        shadow_resolution: u32,
+    ) -> LightsRaw {
+        let mut points = self.points.clone();
+
+        if points.len() > MAX_POINT_LIGHTS {
+            points.sort_by(|a, b| {
+                let a = (a.position - eye).magnitude2();
+                let b = (b.position - eye).magnitude2();
+                a.total_cmp(&b)
+            });
+
+            points.truncate(MAX_POINT_LIGHTS);
+        }
+
+        let mut raw_points = [PointLightRaw::zeroed(); MAX_POINT_LIGHTS];
+
+        for (raw, point) in raw_points.iter_mut().zip(&points) {
+            let [r, g, b] = point.color;
+
+            *raw = PointLightRaw {
+                position_radius: [point.position.x, point.position.y, point.position.z, point.radius],
+                color_intensity: [r, g, b, point.intensity],
+            };
+        }
+
+        let direction = self.sun.direction.normalize();
+        let [ambient_r, ambient_g, ambient_b] = self.ambient;
+        let [sun_r, sun_g, sun_b] = self.sun.color;
+
+        LightsRaw {
+            ambient: [ambient_r, ambient_g, ambient_b, 0.0],
+            sun_direction: [direction.x, direction.y, direction.z, 0.0],
+            sun_color: [sun_r, sun_g, sun_b, self.sun.intensity],
+            point_count: [points.len() as u32, 0, 0, 0],
+            shadow_matrix: shadow_matrix.into(),
+            shadow_params: [
+                if self.shadows.enabled { 1.0 } else { 0.0 },
+                self.shadows.bias,
+                1.0 / shadow_resolution as f32,
+                0.0,
+            ],
+            points: raw_points,
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct PointLightRaw {
+    position_radius: [f32; 4],
+    color_intensity: [f32; 4],
+}
+
+impl PointLightRaw {
+    fn zeroed() -> Self {
+        bytemuck::Zeroable::zeroed()
+    }
+}
+
+/// Matches `Lights` in `shader.wgsl`, every field padded to 16 bytes for uniform layout rules.
+#[repr(C)]
+#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct LightsRaw {
+    ambient: [f32; 4],
+    sun_direction: [f32; 4],
+    sun_color: [f32; 4],
+    point_count: [u32; 4],
+    shadow_matrix: [[f32; 4]; 4],
+    /// Enabled flag, depth bias and texel size.
+    shadow_params: [f32; 4],
+    points: [PointLightRaw; MAX_POINT_LIGHTS],
+}
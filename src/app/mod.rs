@@ -1,45 +1,113 @@
 pub mod context;
+pub mod animation;
 pub mod buffers;
-mod camera;
+pub mod builder;
+pub mod camera;
+pub mod camera_controller;
+pub mod debug_draw;
+pub mod geometry;
+pub mod hot_reload;
+pub mod input;
+pub mod light;
+pub mod pipeline;
+pub mod post;
+pub mod render_mode;
+pub mod scene;
+pub mod settings;
+pub mod shader;
+pub mod sprite;
+pub mod text;
+pub mod time_of_day;
+pub mod ui;
+pub mod view;
 mod texture;
-mod matrix;
+mod obj;
+mod picking;
+mod shadow;
+mod uniform;
 
+use std::time::{Duration, Instant};
 use winit::{
     event::*,
-    event_loop::EventLoopWindowTarget,
+    event_loop::{ControlFlow, EventLoopWindowTarget},
     window::Window,
 };
+use winit::dpi::PhysicalSize;
 use winit::keyboard::PhysicalKey;
 
+use builder::AppBuilder;
 use context::Context;
+use settings::Settings;
 use crate::app::context::Renderer;
+use crate::app::time_of_day::GameClock;
 
 
-pub struct App<'a> {
-    game_logic: &'a mut dyn GameLogic,
+pub struct App {
+    game_logic: Box<dyn GameLogic>,
     context : Context,
     inputs: Vec<PhysicalKey>,
+    exiting: bool,
+    last_frame: Instant,
 }
 
 
 pub trait GameLogic {
-    fn render<'a, 'b>(&'a mut self, renderer: &'b mut Renderer<'a>) where 'a : 'b;
+    fn render(&mut self, renderer: &mut Renderer);
 
-    fn init<'a, 'b>(&'a mut self, renderer: &'b mut Renderer<'a>) where 'a : 'b;
+    fn init(&mut self, renderer: &mut Renderer);
 
     fn input(&mut self, inputs: Vec<PhysicalKey>);
+
+    /// Called every frame before `render` with the time since the previous frame in seconds.
+    fn update(&mut self, _dt: f32) {}
+
+    /// Checked after every `update`, returning `true` exits like an accepted close request.
+    fn should_exit(&self) -> bool {
+        false
+    }
+
+    /// Called before `update` whenever a new in-game hour starts, for schedules and shop hours.
+    /// `clock` is at the start of that hour.
+    fn hour_passed(&mut self, _clock: &GameClock) {}
+
+    /// Called when the user asks to close the window.
+    /// Returning `false` vetoes the close, e.g. to ask about unsaved changes.
+    fn close_requested(&mut self) -> bool {
+        true
+    }
+
+    /// Called once before the event loop exits.
+    fn shutdown(&mut self) {}
+
+    /// Called when the window gains or loses keyboard focus, e.g. to pause when alt-tabbing out.
+    fn focus_changed(&mut self, _focused: bool) {}
+
+    /// Called after the surface has been reconfigured for the new size.
+    fn resized(&mut self, _size: PhysicalSize<u32>) {}
+
+    /// Called when the app is sent to the background, mainly on mobile.
+    fn suspended(&mut self) {}
+
+    /// Called when the app comes back after `suspended`, and once at startup.
+    fn resumed(&mut self) {}
 }
 
-impl<'a> App<'a> {
-    pub async fn new(window: Window, game_logic: &'a mut dyn GameLogic) -> App<'a> {
+impl App {
+    pub fn builder(game_logic: Box<dyn GameLogic>) -> AppBuilder {
+        AppBuilder::new(game_logic)
+    }
 
-        let mut context = Context::new(window).await;
-        context.init(game_logic);
+    pub async fn new(window: Window, mut game_logic: Box<dyn GameLogic>, settings: Settings) -> App {
+
+        let mut context = Context::new(window, settings).await;
+        context.init(game_logic.as_mut());
 
         Self {
             game_logic,
             context,
             inputs: Vec::new(),
+            exiting: false,
+            last_frame: Instant::now(),
         }
     }
 
@@ -56,8 +124,28 @@ impl<'a> App<'a> {
 
                 match event {
                     WindowEvent::RedrawRequested => {
+                        let now = Instant::now();
+                        let dt = (now - self.last_frame).as_secs_f32();
+                        self.last_frame = now;
+
                         self.game_logic.input(std::mem::take(&mut self.inputs));
-                        self.context.render(self.game_logic).unwrap();
+
+                        let start = *self.context.clock();
+
+                        // Once per hour, even when a long frame skips over several.
+                        for hours in 1..=self.context.advance_clock(dt) {
+                            self.game_logic.hour_passed(&start.hours_later(hours));
+                        }
+
+                        self.game_logic.update(dt);
+
+                        if self.game_logic.should_exit() {
+                            self.exit(elwt);
+                            return true;
+                        }
+
+                        self.context.render(self.game_logic.as_mut(), dt).unwrap();
+                        self.context.input_mut().end_frame();
                     },
 
                     _ => self.handle_window_event(event, elwt),
@@ -65,9 +153,13 @@ impl<'a> App<'a> {
 
             },
             
-            Event::AboutToWait => {
-                self.context.window().request_redraw();
-            }
+            Event::AboutToWait => self.schedule_redraw(elwt),
+
+            Event::Suspended => self.game_logic.suspended(),
+
+            Event::Resumed => self.game_logic.resumed(),
+
+            Event::LoopExiting => self.exit(elwt),
 
             Event::DeviceEvent {
                 event,
@@ -92,9 +184,12 @@ impl<'a> App<'a> {
         match event {
             WindowEvent::Resized(size) => {
                 self.context.resize(size);
+                self.game_logic.resized(size);
             },
 
-            WindowEvent::CloseRequested => elwt.exit(),
+            WindowEvent::CloseRequested => self.close_requested(elwt),
+
+            WindowEvent::Focused(focused) => self.game_logic.focus_changed(focused),
 
             WindowEvent::KeyboardInput {
                 event: KeyEvent {
@@ -111,13 +206,45 @@ impl<'a> App<'a> {
         }
     }
 
+    fn schedule_redraw(&mut self, elwt: &EventLoopWindowTarget<()>) {
+        let next_frame = match self.context.settings().frame_cap {
+            Some(fps) if fps > 0 => self.last_frame + Duration::from_secs_f64(1.0 / fps as f64),
+            _ => {
+                elwt.set_control_flow(ControlFlow::Poll);
+                self.context.window().request_redraw();
+                return;
+            }
+        };
+
+        if Instant::now() >= next_frame {
+            self.context.window().request_redraw();
+        } else {
+            elwt.set_control_flow(ControlFlow::WaitUntil(next_frame));
+        }
+    }
+
     fn handle_device_event(&mut self, event: DeviceEvent, _elwt: &EventLoopWindowTarget<()>) {
-        match event {
-            _ => {}
+        self.context.input_mut().handle_device_event(&event);
+    }
+
+    fn close_requested(&mut self, elwt: &EventLoopWindowTarget<()>) {
+        if self.game_logic.close_requested() {
+            self.exit(elwt);
         }
     }
+
+    fn exit(&mut self, elwt: &EventLoopWindowTarget<()>) {
+        if self.exiting {
+            return;
+        }
+
+        self.exiting = true;
+        self.game_logic.shutdown();
+        elwt.exit();
+    }
     
-    fn input(&mut self, _event: &WindowEvent) -> bool {
+    fn input(&mut self, event: &WindowEvent) -> bool {
+        self.context.input_mut().handle_window_event(event);
         false
     }
 }
\ No newline at end of file
@@ -0,0 +1,282 @@
+use std::collections::HashMap;
+use cgmath::InnerSpace;
+use crate::app::buffers::Vertex;
+
+/// Where the normal of a vertex comes from, an index into the `vn` lines or the face it's part of.
+#[derive(Copy, Clone, PartialEq, Eq, Hash)]
+enum NormalSource {
+    Index(usize),
+    Face(usize),
+}
+
+/// Parses the subset of Wavefront OBJ exported for props: `v`, `vt`, `vn` and polygon `f` lines.
+/// Polygons are triangulated as fans, materials and groups are ignored.
+pub fn parse(text: &str) -> Option<(Vec<Vertex>, Vec<u16>)> {
+    let mut positions = Vec::new();
+    let mut tex_coords = Vec::new();
+    let mut normals = Vec::new();
+
+    let mut vertices = Vec::new();
+    let mut indices = Vec::new();
+    // Each distinct position/uv/normal combination becomes one vertex.
+    let mut unique = HashMap::new();
+    let mut faces = 0;
+
+    for line in text.lines() {
+        let mut words = line.split_whitespace();
+
+        match words.next() {
+            Some("v") => positions.push(floats::<3>(words)?),
+            Some("vt") => {
+                let [u, v] = floats::<2>(words)?;
+                // OBJ has v pointing up, textures are sampled with v pointing down.
+                tex_coords.push([u, 1.0 - v]);
+            },
+            Some("vn") => normals.push(floats::<3>(words)?),
+            Some("f") => {
+                let corners = words
+                    .map(|corner| corner_indices(corner, positions.len(), tex_coords.len(), normals.len()))
+                    .collect::<Option<Vec<_>>>()?;
+
+                let face_normal = face_normal(corners.iter().map(|&(position, _, _)| positions[position]));
+                let mut polygon = Vec::new();
+
+                for (position, uv, normal) in corners {
+                    // Corners without `vn` take the normal of their face, so they're only shared within it.
+                    let normal = normal.map_or(NormalSource::Face(faces), NormalSource::Index);
+                    let key = (position, uv, normal);
+
+                    let index = match unique.get(&key) {
+                        Some(&index) => index,
+                        None => {
+                            vertices.push(Vertex::new(
+                                positions[position],
+                                uv.map_or([0.0, 0.0], |uv| tex_coords[uv]),
+                                match normal {
+                                    NormalSource::Index(normal) => normals[normal],
+                                    NormalSource::Face(_) => face_normal,
+                                },
+                            ));
+
+                            let index = u16::try_from(vertices.len() - 1).ok()?;
+                            unique.insert(key, index);
+                            index
+                        },
+                    };
+
+                    polygon.push(index);
+                }
+
+                for i in 1..polygon.len().saturating_sub(1) {
+                    indices.extend_from_slice(&[polygon[0], polygon[i], polygon[i + 1]]);
+                }
+
+                faces += 1;
+            },
+            _ => {},
+        }
+    }
+
+    (!indices.is_empty()).then_some((vertices, indices))
+}
+
+fn floats<const N: usize>(mut words: std::str::SplitWhitespace) -> Option<[f32; N]> {
+    let mut values = [0.0; N];
+
+    for value in &mut values {
+        *value = words.next()?.parse().ok()?;
+    }
+
+    Some(values)
+}
+
+/// Newell's method, counter-clockwise polygons face towards the viewer like their triangles.
+/// Degenerate faces point up.
+fn face_normal(corners: impl Iterator<Item = [f32; 3]> + Clone) -> [f32; 3] {
+    let next = corners.clone().cycle().skip(1);
+
+    let normal = corners.zip(next).fold(cgmath::Vector3::new(0.0, 0.0, 0.0), |normal, (a, b)| {
+        normal + cgmath::Vector3::new(
+            (a[1] - b[1]) * (a[2] + b[2]),
+            (a[2] - b[2]) * (a[0] + b[0]),
+            (a[0] - b[0]) * (a[1] + b[1]),
+        )
+    });
+
+    if normal.magnitude2() > 0.0 {
+        normal.normalize().into()
+    } else {
+        [0.0, 1.0, 0.0]
+    }
+}
+
+/// Resolves a `position/uv/normal` corner into zero based indices, negative indices count from the end.
+fn corner_indices(
+    corner: &str,
+    positions: usize,
+    tex_coords: usize,
+    normals: usize,
+) -> Option<(usize, Option<usize>, Option<usize>)> {
+    let resolve = |index: &str, len: usize| -> Option<usize> {
+        let index: i64 = index.parse().ok()?;
+
+        let resolved = if index < 0 {
+            len as i64 + index
+        } else {
+            index - 1
+        };
+
+        (0..len as i64).contains(&resolved).then_some(resolved as usize)
+    };
+
+    let mut parts = corner.split('/');
+
+    let position = resolve(parts.next()?, positions)?;
+
+    let uv = match parts.next() {
+        Some("") | None => None,
+        Some(index) => Some(resolve(index, tex_coords)?),
+    };
+
+    let normal = match parts.next() {
+        Some("") | None => None,
+        Some(index) => Some(resolve(index, normals)?),
+    };
+
+    Some((position, uv, normal))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn unpack(vertex: Vertex) -> ([f32; 3], [f32; 2], [f32; 3]) {
+        let [x, y, z, u, v, nx, ny, nz] = bytemuck::cast::<Vertex, [f32; 8]>(vertex);
+        ([x, y, z], [u, v], [nx, ny, nz])
+    }
+
+    #[test]
+    fn quads_are_triangulated_as_fans() {
+        let (vertices, indices) = parse("
+            v 0 0 0
+            v 1 0 0
+            v 1 1 0
+            v 0 1 0
+            f 1 2 3 4
+        ").unwrap();
+
+        assert_eq!(vertices.len(), 4);
+        assert_eq!(indices, [0, 1, 2, 0, 2, 3]);
+    }
+
+    #[test]
+    fn negative_indices_count_from_the_end() {
+        let (vertices, indices) = parse("
+            v 5 5 5
+            v 0 0 0
+            v 1 0 0
+            v 0 1 0
+            f -3 -2 -1
+        ").unwrap();
+
+        let positions = vertices.into_iter().map(|vertex| unpack(vertex).0).collect::<Vec<_>>();
+
+        assert_eq!(positions, [[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]]);
+        assert_eq!(indices, [0, 1, 2]);
+    }
+
+    #[test]
+    fn corners_with_uv_and_normal() {
+        let (vertices, _) = parse("
+            v 0 0 0
+            v 1 0 0
+            v 0 1 0
+            vt 0.25 0.75
+            vn 0 0 -1
+            f 1/1/1 2/1/1 3/1/1
+        ").unwrap();
+
+        let (_, uv, normal) = unpack(vertices[0]);
+
+        assert_eq!(uv, [0.25, 0.25]);
+        assert_eq!(normal, [0.0, 0.0, -1.0]);
+    }
+
+    #[test]
+    fn corners_with_normal_only() {
+        let (vertices, _) = parse("
+            v 0 0 0
+            v 1 0 0
+            v 0 1 0
+            vn 1 0 0
+            f 1//1 2//1 3//1
+        ").unwrap();
+
+        let (_, uv, normal) = unpack(vertices[0]);
+
+        assert_eq!(uv, [0.0, 0.0]);
+        assert_eq!(normal, [1.0, 0.0, 0.0]);
+    }
+
+    #[test]
+    fn corners_without_normal_use_the_face_normal() {
+        let (vertices, _) = parse("
+            v 0 0 0
+            v 1 0 0
+            v 0 1 0
+            vt 1 1
+            f 1/1 2/1 3/1
+        ").unwrap();
+
+        for vertex in vertices {
+            let (_, uv, normal) = unpack(vertex);
+
+            assert_eq!(uv, [1.0, 0.0]);
+            assert_eq!(normal, [0.0, 0.0, 1.0]);
+        }
+    }
+
+    #[test]
+    fn faces_without_normals_share_no_vertices() {
+        // Two sides of a box meeting at the edge 1-2.
+        let (vertices, indices) = parse("
+            v 0 0 0
+            v 1 0 0
+            v 1 1 0
+            v 1 0 -1
+            f 1 2 3
+            f 2 4 3
+        ").unwrap();
+
+        assert_eq!(vertices.len(), 6);
+        assert_eq!(unpack(vertices[3]).2, [1.0, 0.0, 0.0]);
+        assert_eq!(indices, [0, 1, 2, 3, 4, 5]);
+    }
+
+    #[test]
+    fn shared_corners_with_normals_are_merged() {
+        let (vertices, indices) = parse("
+            v 0 0 0
+            v 1 0 0
+            v 1 1 0
+            v 0 1 0
+            vn 0 0 1
+            f 1//1 2//1 3//1
+            f 1//1 3//1 4//1
+        ").unwrap();
+
+        assert_eq!(vertices.len(), 4);
+        assert_eq!(indices, [0, 1, 2, 0, 2, 3]);
+    }
+
+    #[test]
+    fn out_of_range_index_fails() {
+        assert!(parse("v 0 0 0\nf 1 2 3").is_none());
+        assert!(parse("v 0 0 0\nv 1 0 0\nv 0 1 0\nf 1/2 2 3").is_none());
+    }
+
+    #[test]
+    fn no_faces_fails() {
+        assert!(parse("v 0 0 0\nv 1 0 0").is_none());
+    }
+}
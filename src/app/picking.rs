@@ -0,0 +1,204 @@
+use std::sync::mpsc;
+use wgpu::StoreOp;
+use crate::app::buffers::Mesh;
+use crate::app::context::RawDrawCallInstanced;
+use crate::app::pipeline::PipelineKey;
+use crate::app::texture::{DepthTexture, Texture};
+
+/// Optional pass that renders the id of every drawn instance into an integer target,
+/// so a single pixel can be read back to find out exactly what is under the cursor.
+pub struct Picking {
+    id_texture: wgpu::Texture,
+    id_view: wgpu::TextureView,
+    depth_texture: DepthTexture,
+    readback: wgpu::Buffer,
+    /// Completion of the readback mapping, `Some` while the copied pixel is being read.
+    mapping: Option<mpsc::Receiver<Result<(), wgpu::BufferAsyncError>>>,
+    width: u32,
+    height: u32,
+}
+
+impl Picking {
+    pub const ID_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::R32Uint;
+
+    pub fn new(device: &wgpu::Device, config: &wgpu::SurfaceConfiguration) -> Self {
+        let id_texture = Self::create_id_texture(device, config);
+        let id_view = id_texture.create_view(&wgpu::TextureViewDescriptor::default());
+
+        let readback = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Picking readback buffer"),
+            size: wgpu::COPY_BYTES_PER_ROW_ALIGNMENT as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+
+        Self {
+            id_texture,
+            id_view,
+            depth_texture: DepthTexture::new(device, config, 1, "picking depth texture"),
+            readback,
+            mapping: None,
+            width: config.width,
+            height: config.height,
+        }
+    }
+
+    pub fn is_sized_for(&self, config: &wgpu::SurfaceConfiguration) -> bool {
+        self.width == config.width && self.height == config.height
+    }
+
+    pub fn pipeline_key() -> PipelineKey {
+        PipelineKey::new("picking.wgsl", Some(Self::ID_FORMAT))
+    }
+
+    pub fn create_pipeline_layout(device: &wgpu::Device, camera_layout: &wgpu::BindGroupLayout) -> wgpu::PipelineLayout {
+        device.create_pipeline_layout(
+            &wgpu::PipelineLayoutDescriptor {
+                label: Some("Picking pipeline"),
+                bind_group_layouts: &[&Texture::create_bind_group_layout(device), camera_layout],
+                push_constant_ranges: &[],
+            }
+        )
+    }
+
+    /// Renders the id pass and copies the pixel at `(x, y)` into the readback buffer.
+    #[allow(clippy::too_many_arguments)]
+    pub fn render(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        pipeline: &wgpu::RenderPipeline,
+        draw_calls: &[RawDrawCallInstanced],
+        meshes: &[Mesh],
+        textures: &[Texture],
+        camera_bind_group: &wgpu::BindGroup,
+        viewport: [f32; 4],
+        (x, y): (u32, u32),
+    ) {
+        {
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Picking pass"),
+                color_attachments: &[
+                    Some(wgpu::RenderPassColorAttachment {
+                        view: &self.id_view,
+                        resolve_target: None,
+                        ops: wgpu::Operations {
+                            load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                            store: StoreOp::Store,
+                        },
+                    })
+                ],
+                depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                    view: &self.depth_texture.view,
+                    depth_ops: Some(wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(1.0),
+                        store: StoreOp::Discard,
+                    }),
+                    stencil_ops: None,
+                }),
+                occlusion_query_set: None,
+                timestamp_writes: None,
+            });
+
+            render_pass.set_pipeline(pipeline);
+
+            let [viewport_x, viewport_y, width, height] = viewport;
+            render_pass.set_viewport(viewport_x, viewport_y, width, height, 0.0, 1.0);
+
+            for draw_call in draw_calls {
+                let texture = &textures[draw_call.params.texture_id];
+                let mesh = &meshes[draw_call.params.mesh_id];
+
+                render_pass.set_vertex_buffer(1, draw_call.buffer.slice(..));
+                render_pass.set_bind_group(1, camera_bind_group, &[]);
+                mesh.draw(texture, &mut render_pass, 0..draw_call.range);
+            }
+        }
+
+        encoder.copy_texture_to_buffer(
+            wgpu::ImageCopyTexture {
+                texture: &self.id_texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d {
+                    x: x.min(self.width - 1),
+                    y: y.min(self.height - 1),
+                    z: 0,
+                },
+                aspect: wgpu::TextureAspect::All,
+            },
+            wgpu::ImageCopyBuffer {
+                buffer: &self.readback,
+                layout: wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT),
+                    rows_per_image: Some(1),
+                },
+            },
+            wgpu::Extent3d {
+                width: 1,
+                height: 1,
+                depth_or_array_layers: 1,
+            },
+        );
+    }
+
+    /// The readback buffer is mapped, no new pixel can be copied into it until `poll` reports the result.
+    pub fn is_reading(&self) -> bool {
+        self.mapping.is_some()
+    }
+
+    /// Starts mapping the copied pixel without waiting for the GPU. Must be called after the encoder is submitted.
+    pub fn start_read(&mut self) {
+        let (sender, receiver) = mpsc::channel();
+
+        self.readback.slice(..).map_async(wgpu::MapMode::Read, move |result| {
+            let _ = sender.send(result);
+        });
+
+        self.mapping = Some(receiver);
+    }
+
+    /// Returns `Some` once the pixel of `start_read` is available, with the id found there if any.
+    pub fn poll(&mut self, device: &wgpu::Device) -> Option<Option<u32>> {
+        let receiver = self.mapping.as_ref()?;
+        device.poll(wgpu::Maintain::Poll);
+
+        let result = match receiver.try_recv() {
+            Ok(result) => result,
+            Err(mpsc::TryRecvError::Empty) => return None,
+            Err(mpsc::TryRecvError::Disconnected) => Err(wgpu::BufferAsyncError),
+        };
+
+        self.mapping = None;
+
+        if let Err(error) = result {
+            log::warn!("Can't read the picking buffer: {error}");
+            return Some(None);
+        }
+
+        let id = {
+            let data = self.readback.slice(..).get_mapped_range();
+            u32::from_le_bytes([data[0], data[1], data[2], data[3]])
+        };
+
+        self.readback.unmap();
+
+        Some(id.checked_sub(1))
+    }
+
+    fn create_id_texture(device: &wgpu::Device, config: &wgpu::SurfaceConfiguration) -> wgpu::Texture {
+        device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Picking id texture"),
+            size: wgpu::Extent3d {
+                width: config.width,
+                height: config.height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: Self::ID_FORMAT,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+            view_formats: &[],
+        })
+    }
+}
@@ -0,0 +1,43 @@
+#include "common.wgsl"
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) tex_coords: vec2<f32>,
+    @location(1) @interpolate(flat) id: u32,
+};
+
+@group(1) @binding(0)
+var<uniform> camera: Camera;
+
+@vertex
+fn vs_main(
+    model: VertexInput,
+    instance: InstanceInput,
+) -> VertexOutput {
+
+    let model_matrix = model_matrix(instance);
+
+    var out: VertexOutput;
+    out.tex_coords = instance_tex_coords(model.tex_coords, instance);
+    out.clip_position = camera.view_projection * model_matrix * vec4<f32>(model.position, 1.0);
+    out.id = instance.id;
+
+    return out;
+}
+
+
+@group(0) @binding(0)
+var t_diffuse: texture_2d<f32>;
+@group(0) @binding(1)
+var s_diffuse: sampler;
+
+// Transparent texels don't count as a hit, so sprites are picked by their silhouette.
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) u32 {
+    if textureSample(t_diffuse, s_diffuse, in.tex_coords).a < 0.5 {
+        discard;
+    }
+
+    // 0 is reserved for "nothing under the cursor".
+    return in.id + 1u;
+}
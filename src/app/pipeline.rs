@@ -0,0 +1,311 @@
+use std::collections::HashMap;
+use crate::app::buffers::{InstanceRaw, Vertex};
+use crate::app::debug_draw::LineVertex;
+use crate::app::hot_reload::validated;
+use crate::app::shader::{Preprocessor, ShaderFeatures};
+use crate::app::texture::DepthTexture;
+
+/// Vertex buffers a pipeline reads, in slot order.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum VertexLayout {
+    /// `Vertex` in slot 0 and `InstanceRaw` in slot 1, used by every mesh pass.
+    MeshInstanced,
+    /// No buffers, the vertex shader generates a triangle covering the viewport.
+    Fullscreen,
+    /// `LineVertex` in slot 0, used by debug lines.
+    Line,
+}
+
+impl VertexLayout {
+    pub fn buffers(&self) -> Vec<wgpu::VertexBufferLayout<'static>> {
+        match self {
+            VertexLayout::MeshInstanced => vec![Vertex::desc(), InstanceRaw::desc()],
+            VertexLayout::Fullscreen => vec![],
+            VertexLayout::Line => vec![LineVertex::desc()],
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum BlendMode {
+    /// Overwrites the target, also the only mode allowed for integer formats.
+    Opaque,
+    Alpha,
+    Additive,
+    /// Writes the blend constant set on the render pass, the shader output is ignored.
+    Constant,
+}
+
+impl BlendMode {
+    fn to_wgpu(self) -> Option<wgpu::BlendState> {
+        match self {
+            BlendMode::Opaque => None,
+            BlendMode::Alpha => Some(wgpu::BlendState::ALPHA_BLENDING),
+            BlendMode::Additive => Some(wgpu::BlendState {
+                color: wgpu::BlendComponent {
+                    src_factor: wgpu::BlendFactor::SrcAlpha,
+                    dst_factor: wgpu::BlendFactor::One,
+                    operation: wgpu::BlendOperation::Add,
+                },
+                alpha: wgpu::BlendComponent::OVER,
+            }),
+            BlendMode::Constant => {
+                let constant = wgpu::BlendComponent {
+                    src_factor: wgpu::BlendFactor::Constant,
+                    dst_factor: wgpu::BlendFactor::Zero,
+                    operation: wgpu::BlendOperation::Add,
+                };
+
+                Some(wgpu::BlendState {
+                    color: constant,
+                    alpha: constant,
+                })
+            },
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum DepthMode {
+    /// The pass has no depth attachment.
+    Disabled,
+    /// Tested against the scene without writing, for transparent surfaces and overlays.
+    ReadOnly,
+    ReadWrite,
+    /// Ignores the depth attachment of the pass, so it's drawn over everything before it.
+    Overlay,
+    /// Written with a slope scaled bias, keeps surfaces at grazing sun angles from shadowing themselves.
+    ShadowCaster,
+    /// Always passes and writes, resets the depth of the covered area.
+    Reset,
+}
+
+impl DepthMode {
+    fn to_wgpu(self) -> Option<wgpu::DepthStencilState> {
+        let (depth_write_enabled, depth_compare, bias) = match self {
+            DepthMode::Disabled => return None,
+            DepthMode::ReadOnly => (false, wgpu::CompareFunction::LessEqual, wgpu::DepthBiasState::default()),
+            DepthMode::ReadWrite => (true, wgpu::CompareFunction::Less, wgpu::DepthBiasState::default()),
+            DepthMode::Overlay => (false, wgpu::CompareFunction::Always, wgpu::DepthBiasState::default()),
+            DepthMode::ShadowCaster => {
+                let bias = wgpu::DepthBiasState {
+                    constant: 2,
+                    slope_scale: 2.0,
+                    clamp: 0.0,
+                };
+
+                (true, wgpu::CompareFunction::Less, bias)
+            },
+            DepthMode::Reset => (true, wgpu::CompareFunction::Always, wgpu::DepthBiasState::default()),
+        };
+
+        Some(wgpu::DepthStencilState {
+            format: DepthTexture::DEPTH_FORMAT,
+            depth_write_enabled,
+            depth_compare,
+            stencil: wgpu::StencilState::default(),
+            bias,
+        })
+    }
+}
+
+/// Everything a render pipeline is built from. Pipelines are cached per key.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct PipelineKey {
+    /// Shader file, which also selects the pipeline layout, see `PipelineCache::add_layout`.
+    pub shader: &'static str,
+    pub features: ShaderFeatures,
+    pub vertex_layout: VertexLayout,
+    /// Color target format, `None` for depth only passes.
+    pub format: Option<wgpu::TextureFormat>,
+    pub blend: BlendMode,
+    pub depth: DepthMode,
+    pub cull: Option<wgpu::Face>,
+    pub topology: wgpu::PrimitiveTopology,
+    /// `Line` needs `wgpu::Features::POLYGON_MODE_LINE`.
+    pub polygon_mode: wgpu::PolygonMode,
+    pub sample_count: u32,
+}
+
+impl PipelineKey {
+    /// Opaque, depth tested triangle meshes without culling.
+    pub fn new(shader: &'static str, format: Option<wgpu::TextureFormat>) -> Self {
+        Self {
+            shader,
+            features: ShaderFeatures::default(),
+            vertex_layout: VertexLayout::MeshInstanced,
+            format,
+            blend: BlendMode::Opaque,
+            depth: DepthMode::ReadWrite,
+            cull: None,
+            topology: wgpu::PrimitiveTopology::TriangleList,
+            polygon_mode: wgpu::PolygonMode::Fill,
+            sample_count: 1,
+        }
+    }
+
+    pub fn with_features(mut self, features: ShaderFeatures) -> Self {
+        self.features = features;
+        self
+    }
+
+    pub fn with_vertex_layout(mut self, vertex_layout: VertexLayout) -> Self {
+        self.vertex_layout = vertex_layout;
+        self
+    }
+
+    pub fn with_blend(mut self, blend: BlendMode) -> Self {
+        self.blend = blend;
+        self
+    }
+
+    pub fn with_depth(mut self, depth: DepthMode) -> Self {
+        self.depth = depth;
+        self
+    }
+
+    pub fn with_cull(mut self, cull: Option<wgpu::Face>) -> Self {
+        self.cull = cull;
+        self
+    }
+
+    pub fn with_topology(mut self, topology: wgpu::PrimitiveTopology) -> Self {
+        self.topology = topology;
+        self
+    }
+
+    pub fn with_polygon_mode(mut self, polygon_mode: wgpu::PolygonMode) -> Self {
+        self.polygon_mode = polygon_mode;
+        self
+    }
+
+    pub fn with_sample_count(mut self, sample_count: u32) -> Self {
+        self.sample_count = sample_count;
+        self
+    }
+}
+
+/// Builds render pipelines the first time their key is used and keeps them for later frames.
+pub struct PipelineCache {
+    preprocessor: Preprocessor,
+    layouts: HashMap<&'static str, wgpu::PipelineLayout>,
+    /// `None` when the pipeline failed to build, so the error is logged once.
+    pipelines: HashMap<PipelineKey, Option<wgpu::RenderPipeline>>,
+}
+
+impl PipelineCache {
+    pub fn new(preprocessor: Preprocessor) -> Self {
+        Self {
+            preprocessor,
+            layouts: HashMap::new(),
+            pipelines: HashMap::new(),
+        }
+    }
+
+    /// Sets the bind group layouts used by every pipeline of `shader`.
+    pub fn add_layout(&mut self, shader: &'static str, layout: wgpu::PipelineLayout) {
+        self.layouts.insert(shader, layout);
+    }
+
+    /// Changes where the shader sources are read from, call `rebuild` to apply it to cached pipelines.
+    pub fn set_preprocessor(&mut self, preprocessor: Preprocessor) {
+        self.preprocessor = preprocessor;
+    }
+
+    /// Builds the pipeline for `key` if it isn't cached yet. Errors are logged and the key
+    /// is remembered as failed, `get` then returns `None` until the next rebuild.
+    pub fn prepare(&mut self, device: &wgpu::Device, key: &PipelineKey) {
+        if self.pipelines.contains_key(key) {
+            return;
+        }
+
+        let pipeline = self.create(device, key)
+            .inspect_err(|error| log::error!("Can't build the {} pipeline for {key:?}:\n{error}", key.shader))
+            .ok();
+
+        self.pipelines.insert(*key, pipeline);
+    }
+
+    pub fn get(&self, key: &PipelineKey) -> Option<&wgpu::RenderPipeline> {
+        self.pipelines.get(key)?.as_ref()
+    }
+
+    /// Rebuilds every cached pipeline, keeping all of the old ones if any fails.
+    pub fn rebuild(&mut self, device: &wgpu::Device) -> Result<(), String> {
+        let mut pipelines = HashMap::new();
+
+        for key in self.pipelines.keys() {
+            pipelines.insert(*key, Some(self.create(device, key)?));
+        }
+
+        self.pipelines = pipelines;
+
+        Ok(())
+    }
+
+    pub fn clear(&mut self) {
+        self.pipelines.clear();
+    }
+
+    fn create(&self, device: &wgpu::Device, key: &PipelineKey) -> Result<wgpu::RenderPipeline, String> {
+        let layout = self.layouts.get(key.shader)
+            .ok_or_else(|| format!("no pipeline layout registered for {}", key.shader))?;
+
+        let buffers = key.vertex_layout.buffers();
+
+        let shader = validated(device, || {
+            self.preprocessor.create_module(device, key.shader, &key.features.defines(), &buffers)
+        })
+            .map_err(|error| error.to_string())?
+            .map_err(|error| error.to_string())?;
+
+        let targets: Vec<_> = key.format.into_iter()
+            .map(|format| Some(wgpu::ColorTargetState {
+                format,
+                blend: key.blend.to_wgpu(),
+                write_mask: wgpu::ColorWrites::ALL,
+            }))
+            .collect();
+
+        let vertex_state = wgpu::VertexState {
+            module: &shader,
+            entry_point: "vs_main",
+            buffers: &buffers,
+        };
+
+        let fragment_state = wgpu::FragmentState {
+            module: &shader,
+            entry_point: "fs_main",
+            targets: &targets,
+        };
+
+        let primitive_state = wgpu::PrimitiveState {
+            topology: key.topology,
+            strip_index_format: None,
+            front_face: wgpu::FrontFace::Ccw,
+            cull_mode: key.cull,
+            polygon_mode: key.polygon_mode,
+            unclipped_depth: false,
+            conservative: false,
+        };
+
+        validated(device, || {
+            device.create_render_pipeline(
+                &wgpu::RenderPipelineDescriptor {
+                    label: Some(key.shader),
+                    layout: Some(layout),
+                    vertex: vertex_state,
+                    fragment: Some(fragment_state),
+                    primitive: primitive_state,
+                    depth_stencil: key.depth.to_wgpu(),
+                    multisample: wgpu::MultisampleState {
+                        count: key.sample_count,
+                        mask: !0,
+                        alpha_to_coverage_enabled: false,
+                    },
+                    multiview: None,
+                }
+            )
+        }).map_err(|error| error.to_string())
+    }
+}
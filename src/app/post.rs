@@ -0,0 +1,481 @@
+use std::collections::HashMap;
+use bytemuck::Zeroable;
+use wgpu::StoreOp;
+use crate::app::hot_reload::validated;
+use crate::app::texture::Texture;
+use crate::app::uniform::Uniform;
+
+/// Format of the intermediate targets, so lit colors can go above 1.0 until they are tonemapped.
+pub const HDR_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+
+/// One full-screen pass of the post-processing chain.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum PostEffect {
+    /// Maps HDR colors into the displayable range, should run before the other effects.
+    Tonemap { exposure: f32 },
+    /// Looks the colors up in a LUT added with `Renderer::add_lut`, for seasonal moods.
+    /// `strength` blends between the original and the graded colors.
+    ColorGrading { lut: usize, strength: f32 },
+    /// Darkens the corners towards `color`, `radius` and `smoothness` are relative to the half diagonal.
+    Vignette { intensity: f32, radius: f32, smoothness: f32, color: [f32; 3] },
+}
+
+impl PostEffect {
+    pub fn tonemap() -> Self {
+        PostEffect::Tonemap { exposure: 1.0 }
+    }
+
+    pub fn vignette(intensity: f32) -> Self {
+        PostEffect::Vignette {
+            intensity,
+            radius: 0.6,
+            smoothness: 0.5,
+            color: [0.0, 0.0, 0.0],
+        }
+    }
+
+    fn entry_point(&self) -> &'static str {
+        match self {
+            PostEffect::Tonemap { .. } => "fs_tonemap",
+            PostEffect::ColorGrading { .. } => "fs_grade",
+            PostEffect::Vignette { .. } => "fs_vignette",
+        }
+    }
+}
+
+/// Matches `Params` in `post.wgsl`, the meaning of the values depends on the pass.
+#[repr(C)]
+#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct PostParams {
+    values: [f32; 4],
+    color: [f32; 4],
+}
+
+/// 3D color lookup table used by `PostEffect::ColorGrading`.
+pub struct Lut {
+    size: u32,
+    _texture: wgpu::Texture,
+    bind_group: wgpu::BindGroup,
+}
+
+impl Lut {
+    /// LUT that maps every color to itself.
+    pub fn identity(device: &wgpu::Device, queue: &wgpu::Queue, layout: &wgpu::BindGroupLayout, size: u32) -> Self {
+        let max = (size - 1) as f32;
+        let mut data = Vec::with_capacity((size * size * size * 4) as usize);
+
+        for b in 0..size {
+            for g in 0..size {
+                for r in 0..size {
+                    let channel = |value: u32| (value as f32 / max * 255.0).round() as u8;
+                    data.extend_from_slice(&[channel(r), channel(g), channel(b), 255]);
+                }
+            }
+        }
+
+        Self::create(device, queue, layout, size, &data)
+    }
+
+    /// Loads the common horizontal strip layout: `size` slices of `size` x `size`, blue increasing
+    /// from left to right. A 16 sized LUT is a 256 x 16 image.
+    pub fn from_strip(
+        bytes: &[u8],
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        layout: &wgpu::BindGroupLayout,
+    ) -> Option<Self> {
+        let image = image::load_from_memory(bytes).ok()?.to_rgba8();
+        let size = image.height();
+
+        if size < 2 || image.width() != size * size {
+            log::warn!("LUT strip must be size * size by size pixels, got {}x{}", image.width(), size);
+            return None;
+        }
+
+        let mut data = Vec::with_capacity((size * size * size * 4) as usize);
+
+        for b in 0..size {
+            for g in 0..size {
+                for r in 0..size {
+                    data.extend_from_slice(&image.get_pixel(b * size + r, g).0);
+                }
+            }
+        }
+
+        Some(Self::create(device, queue, layout, size, &data))
+    }
+
+    fn create(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        layout: &wgpu::BindGroupLayout,
+        size: u32,
+        data: &[u8],
+    ) -> Self {
+        let extent = wgpu::Extent3d {
+            width: size,
+            height: size,
+            depth_or_array_layers: size,
+        };
+
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Color grading LUT"),
+            size: extent,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D3,
+            format: wgpu::TextureFormat::Rgba8Unorm,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+
+        queue.write_texture(
+            wgpu::ImageCopyTexture {
+                texture: &texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d::ZERO,
+                aspect: wgpu::TextureAspect::All,
+            },
+            data,
+            wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: Some(4 * size),
+                rows_per_image: Some(size),
+            },
+            extent,
+        );
+
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+
+        let edge = wgpu::AddressMode::ClampToEdge;
+
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            address_mode_u: edge,
+            address_mode_v: edge,
+            address_mode_w: edge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("LUT bind group"),
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&sampler),
+                },
+            ],
+        });
+
+        Self {
+            size,
+            _texture: texture,
+            bind_group,
+        }
+    }
+
+    fn create_bind_group_layout(device: &wgpu::Device) -> wgpu::BindGroupLayout {
+        device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("LUT bind group layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::D3,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+        })
+    }
+}
+
+/// HDR color target that is rendered into by one pass and sampled by the next.
+struct ColorTarget {
+    view: wgpu::TextureView,
+    texture: Texture,
+}
+
+impl ColorTarget {
+    fn new(device: &wgpu::Device, config: &wgpu::SurfaceConfiguration) -> Self {
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Post-processing target"),
+            size: wgpu::Extent3d {
+                width: config.width,
+                height: config.height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: HDR_FORMAT,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        });
+
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+
+        Self {
+            view,
+            texture: Texture::from_texture(texture, device),
+        }
+    }
+}
+
+/// The scene is rendered into an HDR target, then every effect runs as a full-screen pass
+/// ping-ponging between two targets, and a last pass applies the screen fade onto the surface.
+pub struct PostProcessing {
+    pub effects: Vec<PostEffect>,
+    targets: [ColorTarget; 2],
+    luts: Vec<Lut>,
+    lut_layout: wgpu::BindGroupLayout,
+    pipeline_layout: wgpu::PipelineLayout,
+    pipelines: HashMap<&'static str, wgpu::RenderPipeline>,
+    output_pipeline: wgpu::RenderPipeline,
+    /// One per pass, queue writes to a shared buffer would all land before the first pass runs.
+    params: Vec<Uniform<PostParams>>,
+}
+
+impl PostProcessing {
+    /// Id of the identity LUT, always present.
+    pub const IDENTITY_LUT: usize = 0;
+
+    pub fn new(device: &wgpu::Device, queue: &wgpu::Queue, config: &wgpu::SurfaceConfiguration) -> Self {
+        let shader = device.create_shader_module(
+            wgpu::include_wgsl!("post.wgsl")
+        );
+
+        let lut_layout = Lut::create_bind_group_layout(device);
+
+        let pipeline_layout = device.create_pipeline_layout(
+            &wgpu::PipelineLayoutDescriptor {
+                label: Some("Post-processing pipeline"),
+                bind_group_layouts: &[
+                    &Texture::create_bind_group_layout(device),
+                    &Uniform::<PostParams>::create_bind_group_layout(device, wgpu::ShaderStages::FRAGMENT, "Post params"),
+                    &lut_layout,
+                ],
+                push_constant_ranges: &[],
+            }
+        );
+
+        let (pipelines, output_pipeline) = Self::create_pipelines(device, &shader, &pipeline_layout, config.format);
+
+        let identity = Lut::identity(device, queue, &lut_layout, 16);
+
+        Self {
+            effects: vec![PostEffect::tonemap(), PostEffect::vignette(0.25)],
+            targets: [ColorTarget::new(device, config), ColorTarget::new(device, config)],
+            luts: vec![identity],
+            lut_layout,
+            pipeline_layout,
+            pipelines,
+            output_pipeline,
+            params: Vec::new(),
+        }
+    }
+
+    /// Rebuilds every pass from a new version of `post.wgsl`, keeping the old ones if it's invalid.
+    pub fn set_shader(
+        &mut self,
+        device: &wgpu::Device,
+        shader: &wgpu::ShaderModule,
+        output_format: wgpu::TextureFormat,
+    ) -> Result<(), wgpu::Error> {
+        let (pipelines, output_pipeline) = validated(device, || {
+            Self::create_pipelines(device, shader, &self.pipeline_layout, output_format)
+        })?;
+
+        self.pipelines = pipelines;
+        self.output_pipeline = output_pipeline;
+
+        Ok(())
+    }
+
+    fn create_pipelines(
+        device: &wgpu::Device,
+        shader: &wgpu::ShaderModule,
+        pipeline_layout: &wgpu::PipelineLayout,
+        output_format: wgpu::TextureFormat,
+    ) -> (HashMap<&'static str, wgpu::RenderPipeline>, wgpu::RenderPipeline) {
+        let pipelines = ["fs_tonemap", "fs_grade", "fs_vignette"]
+            .into_iter()
+            .map(|entry_point| {
+                let pipeline = Self::create_pipeline(device, shader, pipeline_layout, entry_point, HDR_FORMAT);
+                (entry_point, pipeline)
+            })
+            .collect();
+
+        let output_pipeline = Self::create_pipeline(device, shader, pipeline_layout, "fs_output", output_format);
+
+        (pipelines, output_pipeline)
+    }
+
+    pub fn resize(&mut self, device: &wgpu::Device, config: &wgpu::SurfaceConfiguration) {
+        self.targets = [ColorTarget::new(device, config), ColorTarget::new(device, config)];
+    }
+
+    /// HDR target the scene has to be rendered into.
+    pub fn scene_view(&self) -> &wgpu::TextureView {
+        &self.targets[0].view
+    }
+
+    pub fn lut_layout(&self) -> &wgpu::BindGroupLayout {
+        &self.lut_layout
+    }
+
+    pub fn add_lut(&mut self, lut: Lut) -> usize {
+        self.luts.push(lut);
+        self.luts.len() - 1
+    }
+
+    /// Runs the effects on the scene target and writes the result with `fade` applied to `output`.
+    /// Without `effects` the scene target is passed through as is.
+    pub fn render(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        encoder: &mut wgpu::CommandEncoder,
+        output: &wgpu::TextureView,
+        fade: [f32; 4],
+        effects: bool,
+    ) {
+        let effects = if effects { self.effects.as_slice() } else { &[] };
+
+        while self.params.len() < effects.len() + 1 {
+            self.params.push(Uniform::new(device, PostParams::zeroed(), wgpu::ShaderStages::FRAGMENT, "Post params"));
+        }
+
+        let mut params = self.params.iter_mut();
+        let mut input = 0;
+
+        for effect in effects {
+            let (values, lut) = match *effect {
+                PostEffect::Tonemap { exposure } => (
+                    PostParams { values: [exposure, 0.0, 0.0, 0.0], color: [0.0; 4] },
+                    &self.luts[Self::IDENTITY_LUT],
+                ),
+
+                PostEffect::ColorGrading { lut, strength } => {
+                    let Some(lut) = self.luts.get(lut) else {
+                        continue;
+                    };
+
+                    (PostParams { values: [strength, lut.size as f32, 0.0, 0.0], color: [0.0; 4] }, lut)
+                },
+
+                PostEffect::Vignette { intensity, radius, smoothness, color: [r, g, b] } => (
+                    PostParams { values: [intensity, radius, smoothness, 0.0], color: [r, g, b, 1.0] },
+                    &self.luts[Self::IDENTITY_LUT],
+                ),
+            };
+
+            let output = 1 - input;
+            let uniform = params.next().unwrap();
+            uniform.update(values, queue);
+
+            Self::pass(
+                encoder,
+                &self.pipelines[effect.entry_point()],
+                &self.targets[input].texture,
+                &self.targets[output].view,
+                uniform,
+                lut,
+            );
+
+            input = output;
+        }
+
+        let uniform = params.next().unwrap();
+        uniform.update(PostParams { values: [0.0; 4], color: fade }, queue);
+
+        Self::pass(
+            encoder,
+            &self.output_pipeline,
+            &self.targets[input].texture,
+            output,
+            uniform,
+            &self.luts[Self::IDENTITY_LUT],
+        );
+    }
+
+    fn pass(
+        encoder: &mut wgpu::CommandEncoder,
+        pipeline: &wgpu::RenderPipeline,
+        input: &Texture,
+        output: &wgpu::TextureView,
+        params: &Uniform<PostParams>,
+        lut: &Lut,
+    ) {
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Post-processing pass"),
+            color_attachments: &[
+                Some(wgpu::RenderPassColorAttachment {
+                    view: output,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                        store: StoreOp::Store,
+                    },
+                })
+            ],
+            depth_stencil_attachment: None,
+            occlusion_query_set: None,
+            timestamp_writes: None,
+        });
+
+        render_pass.set_pipeline(pipeline);
+        render_pass.set_bind_group(0, &input.bind_group, &[]);
+        render_pass.set_bind_group(1, &params.bind_group, &[]);
+        render_pass.set_bind_group(2, &lut.bind_group, &[]);
+        render_pass.draw(0..3, 0..1);
+    }
+
+    fn create_pipeline(
+        device: &wgpu::Device,
+        shader: &wgpu::ShaderModule,
+        pipeline_layout: &wgpu::PipelineLayout,
+        entry_point: &str,
+        format: wgpu::TextureFormat,
+    ) -> wgpu::RenderPipeline {
+        device.create_render_pipeline(
+            &wgpu::RenderPipelineDescriptor {
+                label: Some(entry_point),
+                layout: Some(pipeline_layout),
+                vertex: wgpu::VertexState {
+                    module: shader,
+                    entry_point: "vs_main",
+                    buffers: &[],
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: shader,
+                    entry_point,
+                    targets: &[Some(wgpu::ColorTargetState {
+                        format,
+                        blend: None,
+                        write_mask: wgpu::ColorWrites::ALL,
+                    })],
+                }),
+                primitive: wgpu::PrimitiveState::default(),
+                depth_stencil: None,
+                multisample: wgpu::MultisampleState::default(),
+                multiview: None,
+            }
+        )
+    }
+}
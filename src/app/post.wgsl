@@ -0,0 +1,78 @@
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+};
+
+// One triangle covering the whole screen, no vertex buffer needed.
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> VertexOutput {
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+
+    var out: VertexOutput;
+    out.uv = uv;
+    out.clip_position = vec4<f32>(uv * vec2<f32>(2.0, -2.0) + vec2<f32>(-1.0, 1.0), 0.0, 1.0);
+
+    return out;
+}
+
+
+@group(0) @binding(0)
+var t_input: texture_2d<f32>;
+@group(0) @binding(1)
+var s_input: sampler;
+
+struct Params {
+    values: vec4<f32>,
+    color: vec4<f32>,
+};
+
+@group(1) @binding(0)
+var<uniform> params: Params;
+
+@group(2) @binding(0)
+var t_lut: texture_3d<f32>;
+@group(2) @binding(1)
+var s_lut: sampler;
+
+// Narkowicz's fit of the ACES filmic curve. values.x is the exposure.
+@fragment
+fn fs_tonemap(in: VertexOutput) -> @location(0) vec4<f32> {
+    let color = textureSample(t_input, s_input, in.uv);
+    let x = color.rgb * params.values.x;
+
+    let mapped = (x * (2.51 * x + 0.03)) / (x * (2.43 * x + 0.59) + 0.14);
+    return vec4<f32>(clamp(mapped, vec3<f32>(0.0), vec3<f32>(1.0)), color.a);
+}
+
+// LUTs are authored on gamma encoded images, so the lookup happens in that space.
+// values.x is the strength and values.y the LUT size.
+@fragment
+fn fs_grade(in: VertexOutput) -> @location(0) vec4<f32> {
+    let color = textureSample(t_input, s_input, in.uv);
+    let encoded = pow(clamp(color.rgb, vec3<f32>(0.0), vec3<f32>(1.0)), vec3<f32>(1.0 / 2.2));
+
+    // Sample texel centers so the edges of the LUT aren't blended with the border.
+    let size = params.values.y;
+    let coords = encoded * ((size - 1.0) / size) + 0.5 / size;
+    let graded = pow(textureSample(t_lut, s_lut, coords).rgb, vec3<f32>(2.2));
+
+    return vec4<f32>(mix(color.rgb, graded, params.values.x), color.a);
+}
+
+// values: intensity, radius and smoothness. Darkens towards color.rgb.
+@fragment
+fn fs_vignette(in: VertexOutput) -> @location(0) vec4<f32> {
+    let color = textureSample(t_input, s_input, in.uv);
+
+    let distance = length(in.uv - vec2<f32>(0.5)) * 1.41421356;
+    let amount = smoothstep(params.values.y, params.values.y + params.values.z, distance) * params.values.x;
+
+    return vec4<f32>(mix(color.rgb, params.color.rgb, amount), color.a);
+}
+
+// Last pass, writes to the surface and blends towards the fade color by color.a.
+@fragment
+fn fs_output(in: VertexOutput) -> @location(0) vec4<f32> {
+    let color = textureSample(t_input, s_input, in.uv);
+    return vec4<f32>(mix(color.rgb, params.color.rgb, params.color.a), 1.0);
+}
@@ -0,0 +1,188 @@
+use wgpu::StoreOp;
+use crate::app::camera::{Camera, Projection};
+use crate::app::pipeline::{BlendMode, DepthMode, PipelineKey, VertexLayout};
+use crate::app::post::HDR_FORMAT;
+use crate::app::shader::ShaderFeatures;
+use crate::app::uniform::Uniform;
+
+/// How the scene is drawn, switched at runtime to debug meshes and performance.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub enum RenderMode {
+    #[default]
+    Shaded,
+    /// Triangle edges only, needs `wgpu::Features::POLYGON_MODE_LINE`.
+    Wireframe,
+    /// A checker pattern tinted by the texture coordinates instead of the texture.
+    UvChecker,
+    /// The main depth buffer in grayscale, white is near and black is the far plane.
+    Depth,
+    /// Every fragment adds a bit of heat on black without depth testing or post effects,
+    /// bright areas are drawn many times.
+    Overdraw,
+}
+
+impl RenderMode {
+    pub const ALL: [RenderMode; 5] = [
+        RenderMode::Shaded,
+        RenderMode::Wireframe,
+        RenderMode::UvChecker,
+        RenderMode::Depth,
+        RenderMode::Overdraw,
+    ];
+
+    /// The following mode, wrapping around, handy to cycle with a single key.
+    pub fn next(self) -> Self {
+        let index = Self::ALL.iter().position(|&mode| mode == self).unwrap_or(0);
+        Self::ALL[(index + 1) % Self::ALL.len()]
+    }
+
+    /// Overdraw heat starts from black, so the sky doesn't tint it.
+    pub(crate) fn clear_color(self, color: wgpu::Color) -> wgpu::Color {
+        match self {
+            RenderMode::Overdraw => wgpu::Color::BLACK,
+            _ => color,
+        }
+    }
+
+    /// Post effects are skipped when they would distort the debug output, e.g. the overdraw heatmap.
+    pub(crate) fn post_effects(self) -> bool {
+        self != RenderMode::Overdraw
+    }
+
+    /// Applies the mode to the pipeline of a scene draw.
+    pub(crate) fn apply(self, key: PipelineKey) -> PipelineKey {
+        match self {
+            RenderMode::Shaded | RenderMode::Depth => key,
+            RenderMode::Wireframe => key.with_polygon_mode(wgpu::PolygonMode::Line),
+            RenderMode::UvChecker => key.with_features(ShaderFeatures {
+                uv_checker: true,
+                ..key.features
+            }),
+            RenderMode::Overdraw => key
+                .with_features(ShaderFeatures {
+                    overdraw: true,
+                    ..key.features
+                })
+                .with_blend(BlendMode::Additive)
+                .with_depth(DepthMode::Overlay),
+        }
+    }
+}
+
+/// Fullscreen pass drawing the main depth buffer over the main viewport for `RenderMode::Depth`.
+pub struct DepthView {
+    multisampled: bool,
+    /// Near plane, far plane and whether the projection is orthographic.
+    params: Uniform<[f32; 4]>,
+}
+
+impl DepthView {
+    pub const SHADER: &'static str = "depth_view.wgsl";
+
+    pub fn new(device: &wgpu::Device, sample_count: u32) -> Self {
+        Self {
+            multisampled: sample_count > 1,
+            params: Uniform::new(device, [0.0; 4], wgpu::ShaderStages::FRAGMENT, "Depth view"),
+        }
+    }
+
+    pub fn pipeline_key(&self) -> PipelineKey {
+        PipelineKey::new(Self::SHADER, Some(HDR_FORMAT))
+            .with_features(ShaderFeatures {
+                multisampled: self.multisampled,
+                ..ShaderFeatures::default()
+            })
+            .with_vertex_layout(VertexLayout::Fullscreen)
+            .with_depth(DepthMode::Disabled)
+    }
+
+    /// Depth texture at group 0 and the camera planes at group 1.
+    pub fn create_pipeline_layout(&self, device: &wgpu::Device) -> wgpu::PipelineLayout {
+        let depth_layout = Self::create_bind_group_layout(device, self.multisampled);
+
+        let params_layout = Uniform::<[f32; 4]>::create_bind_group_layout(
+            device,
+            wgpu::ShaderStages::FRAGMENT,
+            "Depth view",
+        );
+
+        device.create_pipeline_layout(
+            &wgpu::PipelineLayoutDescriptor {
+                label: Some("Depth view pipeline"),
+                bind_group_layouts: &[&depth_layout, &params_layout],
+                push_constant_ranges: &[],
+            }
+        )
+    }
+
+    /// `camera` and `viewport` are the ones of the main view, other views keep their shading.
+    #[allow(clippy::too_many_arguments)]
+    pub fn render(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        encoder: &mut wgpu::CommandEncoder,
+        pipeline: &wgpu::RenderPipeline,
+        depth: &wgpu::TextureView,
+        camera: &Camera,
+        viewport: [f32; 4],
+        output: &wgpu::TextureView,
+    ) {
+        let orthographic = matches!(camera.projection, Projection::Orthographic { .. });
+        self.params.update([camera.z_near, camera.z_far, orthographic as u32 as f32, 0.0], queue);
+
+        let depth_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Depth view bind group"),
+            layout: &Self::create_bind_group_layout(device, self.multisampled),
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(depth),
+                },
+            ],
+        });
+
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Depth view pass"),
+            color_attachments: &[
+                Some(wgpu::RenderPassColorAttachment {
+                    view: output,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Load,
+                        store: StoreOp::Store,
+                    },
+                })
+            ],
+            depth_stencil_attachment: None,
+            occlusion_query_set: None,
+            timestamp_writes: None,
+        });
+
+        let [x, y, width, height] = viewport;
+        render_pass.set_viewport(x, y, width, height, 0.0, 1.0);
+
+        render_pass.set_pipeline(pipeline);
+        render_pass.set_bind_group(0, &depth_bind_group, &[]);
+        render_pass.set_bind_group(1, &self.params.bind_group, &[]);
+        render_pass.draw(0..3, 0..1);
+    }
+
+    fn create_bind_group_layout(device: &wgpu::Device, multisampled: bool) -> wgpu::BindGroupLayout {
+        device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Depth view bind group layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Depth,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled,
+                    },
+                    count: None,
+                },
+            ],
+        })
+    }
+}
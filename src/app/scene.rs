@@ -0,0 +1,280 @@
+use winit::dpi::PhysicalSize;
+use winit::keyboard::PhysicalKey;
+use crate::app::GameLogic;
+use crate::app::context::Renderer;
+use crate::app::time_of_day::GameClock;
+
+/// A single screen of the game: title, farm, house interior, pause menu, shop...
+///
+/// Assets loaded through the `Renderer` live in the shared context, so a scene
+/// can load the same texture path as another one and get the same id back.
+pub trait Scene {
+    /// Called the first frame after the scene is put on the stack.
+    fn init(&mut self, renderer: &mut Renderer);
+
+    /// Called when the scene is popped or replaced.
+    fn cleanup(&mut self) {}
+
+    fn update(&mut self, _dt: f32) -> SceneCommand {
+        SceneCommand::None
+    }
+
+    fn render(&mut self, renderer: &mut Renderer);
+
+    fn input(&mut self, _inputs: Vec<PhysicalKey>) {}
+
+    /// Overlay scenes (pause menu, shop) keep the scene below them rendered.
+    fn is_overlay(&self) -> bool {
+        false
+    }
+
+    fn close_requested(&mut self) -> bool {
+        true
+    }
+
+    fn focus_changed(&mut self, _focused: bool) {}
+
+    fn resized(&mut self, _size: PhysicalSize<u32>) {}
+
+    /// Called on every scene in the stack, the world keeps running behind menus.
+    fn hour_passed(&mut self, _clock: &GameClock) {}
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Transition {
+    Cut,
+    /// Fades to `color` over `duration` seconds, switches scenes and fades back in.
+    Fade { duration: f32, color: [f32; 3] },
+}
+
+impl Transition {
+    pub fn fade(duration: f32) -> Self {
+        Transition::Fade {
+            duration,
+            color: [0.0; 3],
+        }
+    }
+}
+
+pub enum SceneCommand {
+    None,
+    Push(Box<dyn Scene>, Transition),
+    Pop(Transition),
+    Replace(Box<dyn Scene>, Transition),
+}
+
+enum SceneOp {
+    Push(Box<dyn Scene>),
+    Pop,
+    Replace(Box<dyn Scene>),
+}
+
+enum FadeState {
+    Idle,
+    Out { op: SceneOp, time: f32, duration: f32 },
+    In { time: f32, duration: f32 },
+}
+
+struct SceneEntry {
+    scene: Box<dyn Scene>,
+    initialized: bool,
+}
+
+pub struct SceneManager {
+    scenes: Vec<SceneEntry>,
+    fade: FadeState,
+    fade_color: [f32; 3],
+}
+
+impl SceneManager {
+    pub fn new(initial: Box<dyn Scene>) -> Self {
+        Self {
+            scenes: vec![SceneEntry { scene: initial, initialized: false }],
+            fade: FadeState::Idle,
+            fade_color: [0.0; 3],
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.scenes.is_empty()
+    }
+
+    pub fn is_transitioning(&self) -> bool {
+        !matches!(self.fade, FadeState::Idle)
+    }
+
+    fn top(&mut self) -> Option<&mut Box<dyn Scene>> {
+        self.scenes.last_mut().map(|entry| &mut entry.scene)
+    }
+
+    fn execute(&mut self, command: SceneCommand) {
+        let (op, transition) = match command {
+            SceneCommand::None => return,
+            SceneCommand::Push(scene, transition) => (SceneOp::Push(scene), transition),
+            SceneCommand::Pop(transition) => (SceneOp::Pop, transition),
+            SceneCommand::Replace(scene, transition) => (SceneOp::Replace(scene), transition),
+        };
+
+        match transition {
+            Transition::Cut => self.apply(op),
+            Transition::Fade { duration, color } => {
+                self.fade_color = color;
+                self.fade = FadeState::Out {
+                    op,
+                    time: 0.0,
+                    duration: duration / 2.0,
+                };
+            }
+        }
+    }
+
+    fn apply(&mut self, op: SceneOp) {
+        match op {
+            SceneOp::Push(scene) => {
+                self.scenes.push(SceneEntry { scene, initialized: false });
+            },
+
+            SceneOp::Pop => {
+                if let Some(mut entry) = self.scenes.pop() {
+                    entry.scene.cleanup();
+                }
+            },
+
+            SceneOp::Replace(scene) => {
+                if let Some(mut entry) = self.scenes.pop() {
+                    entry.scene.cleanup();
+                }
+
+                self.scenes.push(SceneEntry { scene, initialized: false });
+            },
+        }
+    }
+
+    fn advance_fade(&mut self, dt: f32) {
+        self.fade = match std::mem::replace(&mut self.fade, FadeState::Idle) {
+            FadeState::Out { op, time, duration } if time + dt >= duration => {
+                self.apply(op);
+                FadeState::In { time: 0.0, duration }
+            },
+
+            FadeState::Out { op, time, duration } => FadeState::Out {
+                op,
+                time: time + dt,
+                duration,
+            },
+
+            FadeState::In { time, duration } if time + dt >= duration => FadeState::Idle,
+
+            FadeState::In { time, duration } => FadeState::In {
+                time: time + dt,
+                duration,
+            },
+
+            FadeState::Idle => FadeState::Idle,
+        };
+    }
+
+    fn fade_amount(&self) -> f32 {
+        match &self.fade {
+            FadeState::Idle => 0.0,
+            FadeState::Out { time, duration, .. } => Self::ratio(*time, *duration),
+            FadeState::In { time, duration } => 1.0 - Self::ratio(*time, *duration),
+        }
+    }
+
+    fn ratio(time: f32, duration: f32) -> f32 {
+        if duration > 0.0 {
+            (time / duration).min(1.0)
+        } else {
+            1.0
+        }
+    }
+
+    fn init_pending(&mut self, renderer: &mut Renderer) {
+        for entry in self.scenes.iter_mut().filter(|entry| !entry.initialized) {
+            entry.scene.init(renderer);
+            entry.initialized = true;
+        }
+    }
+
+    /// Index of the lowest scene that is still visible through the overlays above it.
+    fn first_visible(&self) -> usize {
+        self.scenes.iter()
+            .rposition(|entry| !entry.scene.is_overlay())
+            .unwrap_or(0)
+    }
+}
+
+impl GameLogic for SceneManager {
+    fn render(&mut self, renderer: &mut Renderer) {
+        self.init_pending(renderer);
+        renderer.set_fade(self.fade_color, self.fade_amount());
+
+        let first = self.first_visible();
+
+        for entry in self.scenes.iter_mut().skip(first) {
+            entry.scene.render(renderer);
+        }
+    }
+
+    fn init(&mut self, renderer: &mut Renderer) {
+        self.init_pending(renderer);
+    }
+
+    fn input(&mut self, inputs: Vec<PhysicalKey>) {
+        if self.is_transitioning() {
+            return;
+        }
+
+        if let Some(scene) = self.top() {
+            scene.input(inputs);
+        }
+    }
+
+    fn update(&mut self, dt: f32) {
+        if self.is_transitioning() {
+            self.advance_fade(dt);
+            return;
+        }
+
+        let command = match self.top() {
+            Some(scene) => scene.update(dt),
+            None => return,
+        };
+
+        self.execute(command);
+    }
+
+    /// Popping the last scene closes the app, once its transition is over.
+    fn should_exit(&self) -> bool {
+        self.is_empty() && !self.is_transitioning()
+    }
+
+    fn close_requested(&mut self) -> bool {
+        self.top().is_none_or(|scene| scene.close_requested())
+    }
+
+    fn shutdown(&mut self) {
+        while let Some(mut entry) = self.scenes.pop() {
+            entry.scene.cleanup();
+        }
+    }
+
+    fn focus_changed(&mut self, focused: bool) {
+        if let Some(scene) = self.top() {
+            scene.focus_changed(focused);
+        }
+    }
+
+    fn resized(&mut self, size: PhysicalSize<u32>) {
+        for entry in &mut self.scenes {
+            entry.scene.resized(size);
+        }
+    }
+
+    fn hour_passed(&mut self, clock: &GameClock) {
+        for entry in &mut self.scenes {
+            entry.scene.hour_passed(clock);
+        }
+    }
+}
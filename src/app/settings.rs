@@ -0,0 +1,349 @@
+use std::fs;
+use std::str::FromStr;
+use winit::dpi::PhysicalSize;
+use winit::monitor::MonitorHandle;
+use winit::window::Fullscreen;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum WindowMode {
+    Windowed,
+    Borderless,
+    Fullscreen,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum PresentMode {
+    Vsync,
+    NoVsync,
+    /// Low latency without tearing, falls back to `Vsync` when the surface doesn't support it.
+    Mailbox,
+}
+
+/// Engine settings, loaded from a plain `key = value` file.
+///
+/// ```text
+/// # rpg_farm settings
+/// title = RPG Farm
+/// width = 1280
+/// height = 720
+/// window_mode = borderless
+/// present_mode = vsync
+/// msaa = 4
+/// frame_cap = 60
+/// dev_mode = true
+/// shader_directory = src/app
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub struct Settings {
+    pub title: String,
+    pub width: u32,
+    pub height: u32,
+    pub window_mode: WindowMode,
+    pub present_mode: PresentMode,
+    /// 1, 2, 4 or 8, lowered to the highest count the adapter supports.
+    pub msaa_samples: u32,
+    /// Frames per second limit, `None` renders as fast as the present mode allows.
+    pub frame_cap: Option<u32>,
+    /// Loads shaders from `shader_directory` and reloads shaders, textures and meshes when they change.
+    pub dev_mode: bool,
+    /// Where dev mode reads the shader sources, relative to the working directory.
+    pub shader_directory: String,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            title: String::from("rpg_farm"),
+            width: 1280,
+            height: 720,
+            window_mode: WindowMode::Windowed,
+            present_mode: PresentMode::Vsync,
+            msaa_samples: 1,
+            frame_cap: None,
+            dev_mode: false,
+            shader_directory: String::from("src/app"),
+        }
+    }
+}
+
+impl Settings {
+    /// Reads settings from `filepath`, keeping defaults for missing or malformed keys.
+    pub fn load(filepath: &str) -> Option<Self> {
+        let text = fs::read_to_string(filepath).ok()?;
+        Some(Self::parse(&text))
+    }
+
+    pub fn parse(text: &str) -> Self {
+        let mut settings = Self::default();
+        settings.read(text);
+        settings
+    }
+
+    /// Overrides only the keys present in `text`.
+    pub fn read(&mut self, text: &str) {
+        for line in text.lines() {
+            let line = line.trim();
+
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let Some((key, value)) = line.split_once('=') else {
+                log::warn!("Malformed settings line: {line}");
+                continue;
+            };
+
+            if !self.set(key.trim(), value.trim()) {
+                log::warn!("Invalid setting {}: {}", key.trim(), value.trim());
+            }
+        }
+    }
+
+    pub fn save(&self, filepath: &str) -> std::io::Result<()> {
+        fs::write(filepath, self.to_string())
+    }
+
+    pub fn size(&self) -> PhysicalSize<u32> {
+        PhysicalSize::new(self.width, self.height)
+    }
+
+    /// Exclusive fullscreen picks the monitor mode matching the configured size,
+    /// or the biggest one when there is no exact match.
+    pub fn fullscreen(&self, monitor: Option<MonitorHandle>) -> Option<Fullscreen> {
+        match self.window_mode {
+            WindowMode::Windowed => None,
+            WindowMode::Borderless => Some(Fullscreen::Borderless(monitor)),
+            WindowMode::Fullscreen => {
+                let monitor = monitor?;
+
+                let video_mode = monitor.video_modes()
+                    .filter(|mode| mode.size() == self.size())
+                    .max_by_key(|mode| mode.refresh_rate_millihertz())
+                    .or_else(|| monitor.video_modes().max_by_key(|mode| {
+                        let size = mode.size();
+                        (size.width * size.height, mode.refresh_rate_millihertz())
+                    }))?;
+
+                Some(Fullscreen::Exclusive(video_mode))
+            },
+        }
+    }
+
+    fn set(&mut self, key: &str, value: &str) -> bool {
+        match key {
+            "title" => self.title = value.to_string(),
+            // A zero sized surface can't be configured.
+            "width" => {
+                self.width = match value.parse() {
+                    Ok(width @ 1..) => width,
+                    _ => return false,
+                };
+            },
+            "height" => {
+                self.height = match value.parse() {
+                    Ok(height @ 1..) => height,
+                    _ => return false,
+                };
+            },
+            "window_mode" => return parse_into(value, &mut self.window_mode),
+            "present_mode" => return parse_into(value, &mut self.present_mode),
+            "msaa" => {
+                self.msaa_samples = match value.parse() {
+                    Ok(samples @ (1 | 2 | 4 | 8)) => samples,
+                    _ => return false,
+                };
+            },
+            "frame_cap" => {
+                self.frame_cap = match value {
+                    "off" | "0" => None,
+                    _ => match value.parse() {
+                        Ok(fps) => Some(fps),
+                        Err(_) => return false,
+                    },
+                };
+            },
+            "dev_mode" => return parse_into(value, &mut self.dev_mode),
+            "shader_directory" => self.shader_directory = value.to_string(),
+            _ => return false,
+        }
+
+        true
+    }
+}
+
+fn parse_into<T: FromStr>(value: &str, target: &mut T) -> bool {
+    match value.parse() {
+        Ok(parsed) => {
+            *target = parsed;
+            true
+        },
+        Err(_) => false,
+    }
+}
+
+impl std::fmt::Display for Settings {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "title = {}", self.title)?;
+        writeln!(f, "width = {}", self.width)?;
+        writeln!(f, "height = {}", self.height)?;
+        writeln!(f, "window_mode = {}", self.window_mode.name())?;
+        writeln!(f, "present_mode = {}", self.present_mode.name())?;
+        writeln!(f, "msaa = {}", self.msaa_samples)?;
+
+        match self.frame_cap {
+            Some(fps) => writeln!(f, "frame_cap = {fps}")?,
+            None => writeln!(f, "frame_cap = off")?,
+        }
+
+        writeln!(f, "dev_mode = {}", self.dev_mode)?;
+        writeln!(f, "shader_directory = {}", self.shader_directory)?;
+
+        Ok(())
+    }
+}
+
+impl WindowMode {
+    fn name(self) -> &'static str {
+        match self {
+            WindowMode::Windowed => "windowed",
+            WindowMode::Borderless => "borderless",
+            WindowMode::Fullscreen => "fullscreen",
+        }
+    }
+}
+
+impl FromStr for WindowMode {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "windowed" => Ok(WindowMode::Windowed),
+            "borderless" => Ok(WindowMode::Borderless),
+            "fullscreen" => Ok(WindowMode::Fullscreen),
+            _ => Err(()),
+        }
+    }
+}
+
+impl PresentMode {
+    fn name(self) -> &'static str {
+        match self {
+            PresentMode::Vsync => "vsync",
+            PresentMode::NoVsync => "no_vsync",
+            PresentMode::Mailbox => "mailbox",
+        }
+    }
+
+    pub fn to_wgpu(self, supported: &[wgpu::PresentMode]) -> wgpu::PresentMode {
+        match self {
+            PresentMode::Vsync => wgpu::PresentMode::AutoVsync,
+            PresentMode::NoVsync => wgpu::PresentMode::AutoNoVsync,
+            PresentMode::Mailbox if supported.contains(&wgpu::PresentMode::Mailbox) => {
+                wgpu::PresentMode::Mailbox
+            },
+            PresentMode::Mailbox => wgpu::PresentMode::AutoVsync,
+        }
+    }
+}
+
+impl FromStr for PresentMode {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "vsync" | "on" => Ok(PresentMode::Vsync),
+            "no_vsync" | "off" => Ok(PresentMode::NoVsync),
+            "mailbox" => Ok(PresentMode::Mailbox),
+            _ => Err(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_overrides_defaults() {
+        let settings = Settings::parse("\
+# comment
+title = My Farm
+width = 800
+height=600
+window_mode = borderless
+present_mode = off
+msaa = 4
+frame_cap = 30
+dev_mode = true
+shader_directory = assets/shaders
+");
+
+        assert_eq!(settings, Settings {
+            title: String::from("My Farm"),
+            width: 800,
+            height: 600,
+            window_mode: WindowMode::Borderless,
+            present_mode: PresentMode::NoVsync,
+            msaa_samples: 4,
+            frame_cap: Some(30),
+            dev_mode: true,
+            shader_directory: String::from("assets/shaders"),
+        });
+    }
+
+    #[test]
+    fn invalid_values_keep_defaults() {
+        let settings = Settings::parse("\
+width = 0
+height = -5
+window_mode = maximized
+msaa = 3
+frame_cap = fast
+no equals sign
+unknown = 1
+");
+
+        assert_eq!(settings, Settings::default());
+    }
+
+    #[test]
+    fn msaa_accepts_powers_of_two_up_to_eight() {
+        for samples in [1, 2, 4, 8] {
+            assert_eq!(Settings::parse(&format!("msaa = {samples}")).msaa_samples, samples);
+        }
+
+        for samples in [0, 3, 16] {
+            assert_eq!(Settings::parse(&format!("msaa = {samples}")).msaa_samples, 1);
+        }
+    }
+
+    #[test]
+    fn frame_cap_off() {
+        let mut settings = Settings::parse("frame_cap = 60");
+        assert_eq!(settings.frame_cap, Some(60));
+
+        settings.read("frame_cap = off");
+        assert_eq!(settings.frame_cap, None);
+
+        settings.read("frame_cap = 0");
+        assert_eq!(settings.frame_cap, None);
+    }
+
+    #[test]
+    fn display_round_trip() {
+        let settings = Settings {
+            title: String::from("Round trip"),
+            width: 1920,
+            height: 1080,
+            window_mode: WindowMode::Fullscreen,
+            present_mode: PresentMode::Mailbox,
+            msaa_samples: 8,
+            frame_cap: Some(144),
+            dev_mode: true,
+            shader_directory: String::from("shaders"),
+        };
+
+        assert_eq!(Settings::parse(&settings.to_string()), settings);
+        assert_eq!(Settings::parse(&Settings::default().to_string()), Settings::default());
+    }
+}
@@ -0,0 +1,483 @@
+use std::borrow::Cow;
+use std::collections::HashSet;
+use std::fmt;
+use std::path::PathBuf;
+
+/// Shaders compiled into the binary, used when no source directory is set.
+const BUILTIN_SOURCES: &[(&str, &str)] = &[
+    ("common.wgsl", include_str!("common.wgsl")),
+    ("shader.wgsl", include_str!("shader.wgsl")),
+    ("shadow.wgsl", include_str!("shadow.wgsl")),
+    ("picking.wgsl", include_str!("picking.wgsl")),
+    ("clear.wgsl", include_str!("clear.wgsl")),
+    ("debug.wgsl", include_str!("debug.wgsl")),
+    ("depth_view.wgsl", include_str!("depth_view.wgsl")),
+];
+
+/// Optional parts of the main shader, each combination is compiled into its own pipeline.
+#[derive(Copy, Clone, Debug, PartialEq, PartialOrd, Eq, Hash)]
+pub struct ShaderFeatures {
+    /// Shaded by the sun, point lights and shadows, otherwise the texture color is used as is.
+    pub lit: bool,
+    /// Discards texels with alpha under 0.5, for foliage and sprites.
+    pub alpha_test: bool,
+    /// Multiplies the color by the tint of each instance, see `DrawCall::with_tint`.
+    pub instance_tint: bool,
+    /// Outputs a UV checker pattern instead of the shaded color, see `RenderMode::UvChecker`.
+    pub uv_checker: bool,
+    /// Outputs a constant heat for additive blending, see `RenderMode::Overdraw`.
+    pub overdraw: bool,
+    /// Reads a multisampled depth texture, only used by the depth view.
+    pub multisampled: bool,
+}
+
+impl Default for ShaderFeatures {
+    fn default() -> Self {
+        Self {
+            lit: true,
+            alpha_test: false,
+            instance_tint: false,
+            uv_checker: false,
+            overdraw: false,
+            multisampled: false,
+        }
+    }
+}
+
+impl ShaderFeatures {
+    pub fn unlit() -> Self {
+        Self {
+            lit: false,
+            ..Self::default()
+        }
+    }
+
+    pub fn defines(&self) -> Vec<&'static str> {
+        [
+            (self.lit, "LIT"),
+            (self.alpha_test, "ALPHA_TEST"),
+            (self.instance_tint, "INSTANCE_TINT"),
+            (self.uv_checker, "UV_CHECKER"),
+            (self.overdraw, "OVERDRAW"),
+            (self.multisampled, "MULTISAMPLED"),
+        ]
+            .into_iter()
+            .filter_map(|(enabled, define)| enabled.then_some(define))
+            .collect()
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ShaderError {
+    MissingFile(String),
+    /// `#else` or `#endif` without an `#ifdef`, or an `#ifdef` that is never closed.
+    UnbalancedConditional { file: String, line: usize },
+    UnknownDirective { file: String, line: usize, directive: String },
+    /// The shader doesn't parse, or its inputs don't match the vertex buffer layouts.
+    Invalid(String),
+}
+
+impl fmt::Display for ShaderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ShaderError::MissingFile(file) => write!(f, "shader file {file} not found"),
+            ShaderError::UnbalancedConditional { file, line } => {
+                write!(f, "{file}:{line}: unbalanced #ifdef/#else/#endif")
+            },
+            ShaderError::UnknownDirective { file, line, directive } => {
+                write!(f, "{file}:{line}: unknown directive {directive}")
+            },
+            ShaderError::Invalid(message) => write!(f, "{message}"),
+        }
+    }
+}
+
+/// Expands `#include "file.wgsl"`, `#ifdef NAME`, `#ifndef NAME`, `#else` and `#endif`.
+/// Every file is included at most once, so shared structs can be included from anywhere.
+pub struct Preprocessor {
+    /// Directory the sources are read from, `None` uses the built-in copies.
+    directory: Option<PathBuf>,
+}
+
+impl Preprocessor {
+    pub fn builtin() -> Self {
+        Self { directory: None }
+    }
+
+    /// Reads the sources from `directory` on every call, used for hot reloading.
+    pub fn from_directory(directory: impl Into<PathBuf>) -> Self {
+        Self { directory: Some(directory.into()) }
+    }
+
+    /// Names of the sources compiled into the binary, the files watched in dev mode.
+    pub fn builtin_files() -> impl Iterator<Item = &'static str> {
+        BUILTIN_SOURCES.iter().map(|(name, _)| *name)
+    }
+
+    pub fn process(&self, file: &str, defines: &[&str]) -> Result<String, ShaderError> {
+        let mut output = String::new();
+        let mut included = HashSet::new();
+
+        self.expand(file, defines, &mut included, &mut output)?;
+
+        Ok(output)
+    }
+
+    /// Preprocesses `file` and checks its vertex inputs against `buffers` before creating the module.
+    pub fn create_module(
+        &self,
+        device: &wgpu::Device,
+        file: &str,
+        defines: &[&str],
+        buffers: &[wgpu::VertexBufferLayout],
+    ) -> Result<wgpu::ShaderModule, ShaderError> {
+        let source = self.process(file, defines)?;
+
+        let module = wgpu::naga::front::wgsl::parse_str(&source)
+            .map_err(|error| ShaderError::Invalid(error.emit_to_string(&source)))?;
+
+        check_vertex_inputs(&module, buffers)
+            .map_err(|message| ShaderError::Invalid(format!("{file}: {message}")))?;
+
+        Ok(device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some(file),
+            source: wgpu::ShaderSource::Wgsl(source.into()),
+        }))
+    }
+
+    /// Files missing from the directory fall back to the built-in copies,
+    /// so a shader directory only needs the sources being worked on.
+    fn read(&self, file: &str) -> Option<Cow<'static, str>> {
+        let from_disk = self.directory.as_ref()
+            .and_then(|directory| std::fs::read_to_string(directory.join(file)).ok());
+
+        match from_disk {
+            Some(source) => Some(Cow::Owned(source)),
+            None => BUILTIN_SOURCES.iter()
+                .find(|(name, _)| *name == file)
+                .map(|(_, source)| Cow::Borrowed(*source)),
+        }
+    }
+
+    fn expand(
+        &self,
+        file: &str,
+        defines: &[&str],
+        included: &mut HashSet<String>,
+        output: &mut String,
+    ) -> Result<(), ShaderError> {
+        if !included.insert(file.to_string()) {
+            return Ok(());
+        }
+
+        let source = self.read(file).ok_or_else(|| ShaderError::MissingFile(file.to_string()))?;
+
+        // One entry per open #ifdef: whether its current branch is emitted.
+        let mut conditions: Vec<bool> = Vec::new();
+
+        for (index, line) in source.lines().enumerate() {
+            let line_number = index + 1;
+            let trimmed = line.trim();
+            let active = conditions.iter().all(|&condition| condition);
+
+            let unbalanced = || ShaderError::UnbalancedConditional {
+                file: file.to_string(),
+                line: line_number,
+            };
+
+            if !trimmed.starts_with('#') {
+                if active {
+                    output.push_str(line);
+                    output.push('\n');
+                }
+
+                continue;
+            }
+
+            let (directive, argument) = trimmed.split_once(char::is_whitespace)
+                .map(|(directive, argument)| (directive, argument.trim()))
+                .unwrap_or((trimmed, ""));
+
+            match directive {
+                "#ifdef" => conditions.push(defines.contains(&argument)),
+                "#ifndef" => conditions.push(!defines.contains(&argument)),
+                "#else" => {
+                    let condition = conditions.last_mut().ok_or_else(unbalanced)?;
+                    *condition = !*condition;
+                },
+                "#endif" => {
+                    conditions.pop().ok_or_else(unbalanced)?;
+                },
+                "#include" if active => {
+                    let name = argument.trim_matches('"');
+                    self.expand(name, defines, included, output)?;
+                },
+                "#include" => {},
+                _ => return Err(ShaderError::UnknownDirective {
+                    file: file.to_string(),
+                    line: line_number,
+                    directive: directive.to_string(),
+                }),
+            }
+        }
+
+        if !conditions.is_empty() {
+            return Err(ShaderError::UnbalancedConditional {
+                file: file.to_string(),
+                line: source.lines().count(),
+            });
+        }
+
+        Ok(())
+    }
+}
+
+/// Checks that every `@location` input of the vertex entry point is provided by one of the
+/// buffer layouts with the same number of components and scalar kind.
+fn check_vertex_inputs(module: &wgpu::naga::Module, buffers: &[wgpu::VertexBufferLayout]) -> Result<(), String> {
+    use wgpu::naga::{Binding, ScalarKind, TypeInner};
+
+    let Some(entry_point) = module.entry_points.iter().find(|entry| entry.stage == wgpu::naga::ShaderStage::Vertex) else {
+        return Ok(());
+    };
+
+    // (location, name, components, kind) of every input, looking inside struct arguments.
+    let mut inputs = Vec::new();
+
+    let mut collect = |name: Option<&String>, ty: wgpu::naga::Handle<wgpu::naga::Type>, binding: Option<&Binding>| {
+        let Some(Binding::Location { location, .. }) = binding else {
+            return;
+        };
+
+        let shape = match module.types[ty].inner {
+            TypeInner::Scalar { kind, .. } => Some((1, kind)),
+            TypeInner::Vector { size, kind, .. } => Some((size as u32, kind)),
+            _ => None,
+        };
+
+        inputs.push((*location, name.cloned().unwrap_or_default(), shape));
+    };
+
+    for argument in &entry_point.function.arguments {
+        match &module.types[argument.ty].inner {
+            TypeInner::Struct { members, .. } => {
+                for member in members {
+                    collect(member.name.as_ref(), member.ty, member.binding.as_ref());
+                }
+            },
+            _ => collect(argument.name.as_ref(), argument.ty, argument.binding.as_ref()),
+        }
+    }
+
+    for (location, name, shape) in inputs {
+        let attribute = buffers.iter()
+            .flat_map(|buffer| buffer.attributes)
+            .find(|attribute| attribute.shader_location == location)
+            .ok_or_else(|| format!("input {name} at location {location} isn't in any vertex buffer"))?;
+
+        let expected = match attribute.format {
+            wgpu::VertexFormat::Float32 => (1, ScalarKind::Float),
+            wgpu::VertexFormat::Float32x2 => (2, ScalarKind::Float),
+            wgpu::VertexFormat::Float32x3 => (3, ScalarKind::Float),
+            wgpu::VertexFormat::Float32x4 => (4, ScalarKind::Float),
+            wgpu::VertexFormat::Uint32 => (1, ScalarKind::Uint),
+            wgpu::VertexFormat::Uint32x2 => (2, ScalarKind::Uint),
+            wgpu::VertexFormat::Uint32x4 => (4, ScalarKind::Uint),
+            wgpu::VertexFormat::Sint32 => (1, ScalarKind::Sint),
+            // Normalized and packed formats aren't used by the engine layouts.
+            _ => continue,
+        };
+
+        if shape != Some(expected) {
+            return Err(format!(
+                "input {name} at location {location} doesn't match the buffer format {:?}",
+                attribute.format,
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::pipeline::VertexLayout;
+
+    /// Preprocessor reading from a temporary directory, removed when the test ends.
+    struct TempShaders(Preprocessor);
+
+    impl std::ops::Deref for TempShaders {
+        type Target = Preprocessor;
+
+        fn deref(&self) -> &Preprocessor {
+            &self.0
+        }
+    }
+
+    impl Drop for TempShaders {
+        fn drop(&mut self) {
+            if let Some(directory) = &self.0.directory {
+                let _ = std::fs::remove_dir_all(directory);
+            }
+        }
+    }
+
+    /// Preprocessor reading `files` from a temporary directory named after the test.
+    fn preprocessor(test: &str, files: &[(&str, &str)]) -> TempShaders {
+        let directory = std::env::temp_dir().join(format!("shader-tests-{}-{test}", std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+
+        for (name, source) in files {
+            std::fs::write(directory.join(name), source).unwrap();
+        }
+
+        TempShaders(Preprocessor::from_directory(directory))
+    }
+
+    /// Every combination of the optional shader parts.
+    fn permutations() -> Vec<ShaderFeatures> {
+        (0..64)
+            .map(|bits| ShaderFeatures {
+                lit: bits & 1 != 0,
+                alpha_test: bits & 2 != 0,
+                instance_tint: bits & 4 != 0,
+                uv_checker: bits & 8 != 0,
+                overdraw: bits & 16 != 0,
+                multisampled: bits & 32 != 0,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn includes_each_file_once() {
+        let preprocessor = preprocessor("includes", &[
+            ("main.wgsl", "#include \"a.wgsl\"\n#include \"b.wgsl\"\nmain\n"),
+            ("a.wgsl", "#include \"common.wgsl\"\na\n"),
+            ("b.wgsl", "#include \"common.wgsl\"\nb\n"),
+            ("common.wgsl", "common\n"),
+        ]);
+
+        assert_eq!(preprocessor.process("main.wgsl", &[]).unwrap(), "common\na\nb\nmain\n");
+    }
+
+    #[test]
+    fn include_cycle_stops() {
+        let preprocessor = preprocessor("cycle", &[
+            ("a.wgsl", "#include \"b.wgsl\"\na\n"),
+            ("b.wgsl", "#include \"a.wgsl\"\nb\n"),
+        ]);
+
+        assert_eq!(preprocessor.process("a.wgsl", &[]).unwrap(), "b\na\n");
+    }
+
+    #[test]
+    fn missing_include() {
+        let preprocessor = preprocessor("missing", &[("main.wgsl", "#include \"nope.wgsl\"\n")]);
+
+        assert_eq!(
+            preprocessor.process("main.wgsl", &[]),
+            Err(ShaderError::MissingFile("nope.wgsl".to_string())),
+        );
+    }
+
+    #[test]
+    fn nested_conditionals() {
+        let source = "\
+#ifdef LIT
+lit
+#ifndef ALPHA_TEST
+opaque
+#else
+alpha
+#endif
+#else
+unlit
+#ifdef ALPHA_TEST
+unlit_alpha
+#endif
+#endif
+end
+";
+        let preprocessor = preprocessor("nested", &[("main.wgsl", source)]);
+        let process = |defines: &[&str]| preprocessor.process("main.wgsl", defines).unwrap();
+
+        assert_eq!(process(&[]), "unlit\nend\n");
+        assert_eq!(process(&["LIT"]), "lit\nopaque\nend\n");
+        assert_eq!(process(&["LIT", "ALPHA_TEST"]), "lit\nalpha\nend\n");
+        assert_eq!(process(&["ALPHA_TEST"]), "unlit\nunlit_alpha\nend\n");
+    }
+
+    #[test]
+    fn include_inside_inactive_branch_is_skipped() {
+        let preprocessor = preprocessor("inactive_include", &[
+            ("main.wgsl", "#ifdef LIT\n#include \"light.wgsl\"\n#endif\nmain\n"),
+            ("light.wgsl", "light\n"),
+        ]);
+
+        assert_eq!(preprocessor.process("main.wgsl", &[]).unwrap(), "main\n");
+        assert_eq!(preprocessor.process("main.wgsl", &["LIT"]).unwrap(), "light\nmain\n");
+    }
+
+    #[test]
+    fn unbalanced_conditionals() {
+        let preprocessor = preprocessor("unbalanced", &[
+            ("else.wgsl", "a\n#else\n"),
+            ("endif.wgsl", "#endif\n"),
+            ("open.wgsl", "#ifdef LIT\na\n"),
+        ]);
+
+        let unbalanced = |file: &str, line| Err(ShaderError::UnbalancedConditional { file: file.to_string(), line });
+
+        assert_eq!(preprocessor.process("else.wgsl", &[]), unbalanced("else.wgsl", 2));
+        assert_eq!(preprocessor.process("endif.wgsl", &[]), unbalanced("endif.wgsl", 1));
+        assert_eq!(preprocessor.process("open.wgsl", &[]), unbalanced("open.wgsl", 2));
+    }
+
+    #[test]
+    fn unknown_directive() {
+        let preprocessor = preprocessor("unknown", &[("main.wgsl", "a\n  #define LIT\n")]);
+
+        assert_eq!(
+            preprocessor.process("main.wgsl", &[]),
+            Err(ShaderError::UnknownDirective {
+                file: "main.wgsl".to_string(),
+                line: 2,
+                directive: "#define".to_string(),
+            }),
+        );
+    }
+
+    #[test]
+    fn missing_files_fall_back_to_builtin() {
+        let preprocessor = preprocessor("fallback", &[("main.wgsl", "#include \"common.wgsl\"\nmain\n")]);
+        let common = Preprocessor::builtin().process("common.wgsl", &[]).unwrap();
+
+        assert_eq!(preprocessor.process("main.wgsl", &[]).unwrap(), common + "main\n");
+    }
+
+    #[test]
+    fn builtin_shaders_preprocess() {
+        let preprocessor = Preprocessor::builtin();
+
+        for features in permutations() {
+            for file in Preprocessor::builtin_files() {
+                let buffers = match file {
+                    "debug.wgsl" => VertexLayout::Line,
+                    "clear.wgsl" | "depth_view.wgsl" => VertexLayout::Fullscreen,
+                    _ => VertexLayout::MeshInstanced,
+                }.buffers();
+
+                let source = preprocessor.process(file, &features.defines())
+                    .unwrap_or_else(|error| panic!("{features:?} {error}"));
+
+                let module = wgpu::naga::front::wgsl::parse_str(&source)
+                    .unwrap_or_else(|error| panic!("{file} {features:?}:\n{}", error.emit_to_string(&source)));
+
+                if let Err(error) = check_vertex_inputs(&module, &buffers) {
+                    panic!("{file} {features:?}: {error}");
+                }
+            }
+        }
+    }
+}
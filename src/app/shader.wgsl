@@ -1,23 +1,17 @@
-
-struct VertexInput {
-    @location(0) position: vec3<f32>,
-    @location(1) tex_coords: vec2<f32>,
-};
-
-struct InstanceInput {
-    @location(5) model_matrix_0: vec4<f32>,
-    @location(6) model_matrix_1: vec4<f32>,
-    @location(7) model_matrix_2: vec4<f32>,
-    @location(8) model_matrix_3: vec4<f32>,
-}
+#include "common.wgsl"
 
 struct VertexOutput {
     @builtin(position) clip_position: vec4<f32>,
     @location(0) tex_coords: vec2<f32>,
+    @location(1) world_position: vec3<f32>,
+    @location(2) world_normal: vec3<f32>,
+#ifdef INSTANCE_TINT
+    @location(3) tint: vec4<f32>,
+#endif
 };
 
 @group(1) @binding(0)
-var<uniform> camera: mat4x4<f32>;
+var<uniform> camera: Camera;
 
 @vertex
 fn vs_main(
@@ -25,16 +19,25 @@ fn vs_main(
     instance: InstanceInput,
 ) -> VertexOutput {
 
-    let model_matrix = mat4x4<f32> (
-        instance.model_matrix_0,
-        instance.model_matrix_1,
-        instance.model_matrix_2,
-        instance.model_matrix_3,
+    let model_matrix = model_matrix(instance);
+
+    // Fine for uniform scale, non-uniform scale would need the inverse transpose.
+    let normal_matrix = mat3x3<f32>(
+        model_matrix[0].xyz,
+        model_matrix[1].xyz,
+        model_matrix[2].xyz,
     );
 
+    let world_position = model_matrix * vec4<f32>(model.position, 1.0);
+
     var out: VertexOutput;
-    out.tex_coords = model.tex_coords;
-    out.clip_position = camera * model_matrix * vec4<f32>(model.position, 1.0);
+    out.tex_coords = instance_tex_coords(model.tex_coords, instance);
+    out.world_position = world_position.xyz;
+    out.world_normal = normal_matrix * model.normal;
+    out.clip_position = camera.view_projection * world_position;
+#ifdef INSTANCE_TINT
+    out.tint = instance.tint;
+#endif
 
     return out;
 }
@@ -45,7 +48,138 @@ var t_diffuse: texture_2d<f32>;
 @group(0) @binding(1)
 var s_diffuse: sampler;
 
+const MAX_POINT_LIGHTS: u32 = 16u;
+const SPECULAR_STRENGTH: f32 = 0.2;
+const SHININESS: f32 = 32.0;
+
+struct PointLight {
+    position_radius: vec4<f32>,
+    color_intensity: vec4<f32>,
+};
+
+struct Lights {
+    ambient: vec4<f32>,
+    sun_direction: vec4<f32>,
+    sun_color: vec4<f32>,
+    point_count: vec4<u32>,
+    shadow_matrix: mat4x4<f32>,
+    // Enabled flag, depth bias and texel size.
+    shadow_params: vec4<f32>,
+    points: array<PointLight, MAX_POINT_LIGHTS>,
+};
+
+@group(2) @binding(0)
+var<uniform> lights: Lights;
+@group(2) @binding(1)
+var t_shadow: texture_depth_2d;
+@group(2) @binding(2)
+var s_shadow: sampler_comparison;
+
+// Fraction of sun light reaching `world_position`, 3x3 PCF for soft edges.
+fn sun_visibility(world_position: vec3<f32>) -> f32 {
+    if lights.shadow_params.x < 0.5 {
+        return 1.0;
+    }
+
+    let shadow_position = lights.shadow_matrix * vec4<f32>(world_position, 1.0);
+    let uv = shadow_position.xy * vec2<f32>(0.5, -0.5) + vec2<f32>(0.5, 0.5);
+    let depth = shadow_position.z - lights.shadow_params.y;
+
+    // Outside of the shadow box everything is lit.
+    if any(uv < vec2<f32>(0.0)) || any(uv > vec2<f32>(1.0)) || depth > 1.0 {
+        return 1.0;
+    }
+
+    let texel = lights.shadow_params.z;
+    var visibility = 0.0;
+
+    for (var x = -1; x <= 1; x++) {
+        for (var y = -1; y <= 1; y++) {
+            let offset = vec2<f32>(f32(x), f32(y)) * texel;
+            visibility += textureSampleCompareLevel(t_shadow, s_shadow, uv + offset, depth);
+        }
+    }
+
+    return visibility / 9.0;
+}
+
+// Lambert diffuse plus Blinn-Phong specular for light arriving from `to_light`.
+fn shade(normal: vec3<f32>, to_light: vec3<f32>, to_eye: vec3<f32>, color: vec3<f32>) -> vec3<f32> {
+    let diffuse = max(dot(normal, to_light), 0.0);
+    let half_vector = normalize(to_light + to_eye);
+    let specular = pow(max(dot(normal, half_vector), 0.0), SHININESS) * SPECULAR_STRENGTH;
+
+    return color * (diffuse + specular * step(0.0, diffuse));
+}
+
+// Inverse square falloff, windowed so the light reaches exactly zero at its radius.
+fn attenuation(distance: f32, radius: f32) -> f32 {
+    let ratio = distance / radius;
+    let window = clamp(1.0 - ratio * ratio * ratio * ratio, 0.0, 1.0);
+
+    return window * window / (distance * distance + 1.0);
+}
+
+// 8x8 checker over the UV gradient, shows stretching and flipped or mirrored coordinates.
+fn uv_checker(uv: vec2<f32>) -> vec3<f32> {
+    let cell = floor(uv * 8.0);
+    let checker = abs((cell.x + cell.y) % 2.0);
+    let gradient = vec3<f32>(fract(uv), 0.0);
+
+    return mix(gradient * 0.4, gradient * 0.6 + 0.4, checker);
+}
+
+// Added once per fragment, a few layers show red and heavy overdraw turns yellow and white.
+const OVERDRAW_HEAT: vec4<f32> = vec4<f32>(0.1, 0.035, 0.01, 1.0);
+
 @fragment
-fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
-    return textureSample(t_diffuse, s_diffuse, in.tex_coords);
-}
\ No newline at end of file
+fn fs_main(in: VertexOutput, @builtin(front_facing) front_facing: bool) -> @location(0) vec4<f32> {
+    var color = textureSample(t_diffuse, s_diffuse, in.tex_coords);
+
+#ifdef INSTANCE_TINT
+    color *= in.tint;
+#endif
+
+#ifdef ALPHA_TEST
+    if color.a < 0.5 {
+        discard;
+    }
+#endif
+
+#ifdef LIT
+    // Quads and sprites are seen from both sides, so light the back face too.
+    var normal = normalize(in.world_normal);
+    if !front_facing {
+        normal = -normal;
+    }
+
+    let to_eye = normalize(camera.eye.xyz - in.world_position);
+
+    var light = lights.ambient.rgb;
+    let sun = lights.sun_color.rgb * lights.sun_color.a * sun_visibility(in.world_position);
+    light += shade(normal, -lights.sun_direction.xyz, to_eye, sun);
+
+    for (var i = 0u; i < min(lights.point_count.x, MAX_POINT_LIGHTS); i++) {
+        let point = lights.points[i];
+        let offset = point.position_radius.xyz - in.world_position;
+        let distance = length(offset);
+        let radiance = point.color_intensity.rgb * point.color_intensity.a * attenuation(distance, point.position_radius.w);
+
+        light += shade(normal, offset / max(distance, 0.0001), to_eye, radiance);
+    }
+
+    var output = vec4<f32>(color.rgb * light, color.a);
+#else
+    var output = color;
+#endif
+
+#ifdef UV_CHECKER
+    output = vec4<f32>(uv_checker(in.tex_coords), 1.0);
+#endif
+
+#ifdef OVERDRAW
+    output = OVERDRAW_HEAT;
+#endif
+
+    return output;
+}
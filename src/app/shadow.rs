@@ -0,0 +1,139 @@
+use wgpu::StoreOp;
+use crate::app::buffers::Mesh;
+use crate::app::context::RawDrawCallInstanced;
+use crate::app::pipeline::{DepthMode, PipelineKey};
+use crate::app::texture::{DepthTexture, Texture};
+
+/// Depth rendered from the sun, sampled by the main pass through the lighting bind group.
+pub struct ShadowMap {
+    depth_texture: DepthTexture,
+    /// Lights uniform, shadow map and its comparison sampler, bound at group 2 of the main pipeline.
+    pub layout: wgpu::BindGroupLayout,
+    pub bind_group: wgpu::BindGroup,
+}
+
+impl ShadowMap {
+    pub const RESOLUTION: u32 = 2048;
+
+    pub fn new(device: &wgpu::Device, lights_buffer: &wgpu::Buffer) -> Self {
+        let depth_texture = DepthTexture::with_size(
+            device,
+            Self::RESOLUTION,
+            Self::RESOLUTION,
+            1,
+            "shadow map",
+        );
+
+        let layout = Self::create_bind_group_layout(device);
+
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Lighting bind group"),
+            layout: &layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: lights_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(&depth_texture.view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::Sampler(&depth_texture.sampler),
+                },
+            ],
+        });
+
+        Self {
+            depth_texture,
+            layout,
+            bind_group,
+        }
+    }
+
+    pub fn pipeline_key() -> PipelineKey {
+        PipelineKey::new("shadow.wgsl", None).with_depth(DepthMode::ShadowCaster)
+    }
+
+    pub fn create_pipeline_layout(device: &wgpu::Device, camera_layout: &wgpu::BindGroupLayout) -> wgpu::PipelineLayout {
+        device.create_pipeline_layout(
+            &wgpu::PipelineLayoutDescriptor {
+                label: Some("Shadow pipeline"),
+                bind_group_layouts: &[&Texture::create_bind_group_layout(device), camera_layout],
+                push_constant_ranges: &[],
+            }
+        )
+    }
+
+    /// Renders the depth of `draw_calls`, which must be batched with the sun view-projection.
+    pub fn render(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        pipeline: &wgpu::RenderPipeline,
+        draw_calls: &[RawDrawCallInstanced],
+        meshes: &[Mesh],
+        textures: &[Texture],
+        light_bind_group: &wgpu::BindGroup,
+    ) {
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Shadow pass"),
+            color_attachments: &[],
+            depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                view: &self.depth_texture.view,
+                depth_ops: Some(wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(1.0),
+                    store: StoreOp::Store,
+                }),
+                stencil_ops: None,
+            }),
+            occlusion_query_set: None,
+            timestamp_writes: None,
+        });
+
+        render_pass.set_pipeline(pipeline);
+
+        for draw_call in draw_calls {
+            let texture = &textures[draw_call.params.texture_id];
+            let mesh = &meshes[draw_call.params.mesh_id];
+
+            render_pass.set_vertex_buffer(1, draw_call.buffer.slice(..));
+            render_pass.set_bind_group(1, light_bind_group, &[]);
+            mesh.draw(texture, &mut render_pass, 0..draw_call.range);
+        }
+    }
+
+    fn create_bind_group_layout(device: &wgpu::Device) -> wgpu::BindGroupLayout {
+        device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Lighting bind group layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Depth,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Comparison),
+                    count: None,
+                },
+            ],
+        })
+    }
+}
@@ -0,0 +1,39 @@
+#include "common.wgsl"
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) tex_coords: vec2<f32>,
+};
+
+// View-projection of the sun.
+@group(1) @binding(0)
+var<uniform> light: Camera;
+
+@vertex
+fn vs_main(
+    model: VertexInput,
+    instance: InstanceInput,
+) -> VertexOutput {
+
+    let model_matrix = model_matrix(instance);
+
+    var out: VertexOutput;
+    out.tex_coords = instance_tex_coords(model.tex_coords, instance);
+    out.clip_position = light.view_projection * model_matrix * vec4<f32>(model.position, 1.0);
+
+    return out;
+}
+
+
+@group(0) @binding(0)
+var t_diffuse: texture_2d<f32>;
+@group(0) @binding(1)
+var s_diffuse: sampler;
+
+// Only depth is written, transparent texels are cut out so sprite trees cast tree-shaped shadows.
+@fragment
+fn fs_main(in: VertexOutput) {
+    if textureSample(t_diffuse, s_diffuse, in.tex_coords).a < 0.5 {
+        discard;
+    }
+}
@@ -0,0 +1,103 @@
+use crate::app::camera::OPENGL_TO_WGPU_MATRIX;
+use crate::app::geometry::Rect;
+
+/// Orthographic camera of the sprite pass. Sprites are placed in pixels,
+/// the camera scrolls and zooms them as a whole, e.g. to follow the player around the farm.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct SpriteCamera {
+    /// Sprite position shown at the top left corner of the window.
+    pub position: cgmath::Point2<f32>,
+    /// Window pixels per sprite pixel, whole numbers keep pixel art sharp.
+    pub zoom: f32,
+}
+
+impl Default for SpriteCamera {
+    fn default() -> Self {
+        Self {
+            position: cgmath::Point2::new(0.0, 0.0),
+            zoom: 1.0,
+        }
+    }
+}
+
+impl SpriteCamera {
+    /// Projection for a window of `width` by `height` pixels, with y pointing down.
+    pub fn matrix(&self, width: f32, height: f32) -> cgmath::Matrix4<f32> {
+        let (width, height) = (width / self.zoom, height / self.zoom);
+        let (left, top) = (self.position.x, self.position.y);
+
+        OPENGL_TO_WGPU_MATRIX * cgmath::ortho(left, left + width, top + height, top, -1.0, 1.0)
+    }
+
+    /// Sprite position under a point of the window, e.g. the cursor.
+    pub fn screen_to_world(&self, point: cgmath::Point2<f32>) -> cgmath::Point2<f32> {
+        cgmath::Point2::new(
+            self.position.x + point.x / self.zoom,
+            self.position.y + point.y / self.zoom,
+        )
+    }
+}
+
+/// Model matrix of the unit quad covering `rect`, rotated clockwise by `rotation` radians around its center.
+pub(crate) fn quad_matrix(rect: &Rect, rotation: f32) -> cgmath::Matrix4<f32> {
+    let center = rect.center();
+
+    // The quad has y up and pixel coordinates have y down.
+    cgmath::Matrix4::from_translation(cgmath::Vector3::new(center.x, center.y, 0.0))
+        * cgmath::Matrix4::from_angle_z(cgmath::Rad(rotation))
+        * cgmath::Matrix4::from_nonuniform_scale(rect.width, -rect.height, 1.0)
+}
+
+/// Converts a rectangle in texture pixels into the UV rect of `DrawCall::with_uv_rect`.
+pub(crate) fn uv_rect(source: &Rect, texture_width: u32, texture_height: u32) -> [f32; 4] {
+    let (width, height) = (texture_width as f32, texture_height as f32);
+
+    [source.x / width, source.y / height, source.width / width, source.height / height]
+}
+
+#[cfg(test)]
+mod tests {
+    use cgmath::{Point2, Point3, Transform};
+    use super::*;
+
+    const EPSILON: f32 = 1e-4;
+
+    fn assert_near(a: Point3<f32>, b: [f32; 2]) {
+        assert!((a.x - b[0]).abs() < EPSILON && (a.y - b[1]).abs() < EPSILON, "{a:?} != {b:?}");
+    }
+
+    #[test]
+    fn quad_covers_rect() {
+        let matrix = quad_matrix(&Rect::new(10.0, 20.0, 30.0, 40.0), 0.0);
+
+        // Corners of the unit quad, its top left has uv (0, 0).
+        assert_near(matrix.transform_point(Point3::new(-0.5, 0.5, 0.0)), [10.0, 20.0]);
+        assert_near(matrix.transform_point(Point3::new(0.5, -0.5, 0.0)), [40.0, 60.0]);
+    }
+
+    #[test]
+    fn quad_rotates_clockwise_around_center() {
+        let matrix = quad_matrix(&Rect::new(0.0, 0.0, 20.0, 10.0), std::f32::consts::FRAC_PI_2);
+
+        // The middle of the right edge turns to below the center, y points down.
+        assert_near(matrix.transform_point(Point3::new(0.5, 0.0, 0.0)), [10.0, 15.0]);
+        assert_near(matrix.transform_point(Point3::new(0.0, 0.0, 0.0)), [10.0, 5.0]);
+    }
+
+    #[test]
+    fn uv_rect_normalizes_texture_pixels() {
+        assert_eq!(uv_rect(&Rect::new(16.0, 32.0, 16.0, 64.0), 64, 128), [0.25, 0.25, 0.25, 0.5]);
+    }
+
+    #[test]
+    fn screen_to_world_scrolls_and_zooms() {
+        let camera = SpriteCamera {
+            position: Point2::new(100.0, 50.0),
+            zoom: 2.0,
+        };
+
+        assert_eq!(camera.screen_to_world(Point2::new(0.0, 0.0)), Point2::new(100.0, 50.0));
+        assert_eq!(camera.screen_to_world(Point2::new(64.0, 32.0)), Point2::new(132.0, 66.0));
+        assert_eq!(SpriteCamera::default().screen_to_world(Point2::new(7.0, 9.0)), Point2::new(7.0, 9.0));
+    }
+}
@@ -0,0 +1,403 @@
+use std::collections::HashMap;
+use std::ops::Range;
+use ab_glyph::{Font as _, FontVec, GlyphId, PxScale, PxScaleFont, ScaleFont};
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub enum TextAlign {
+    /// Lines start at the text position.
+    #[default]
+    Left,
+    /// Lines are centered on the text position.
+    Center,
+    /// Lines end at the text position.
+    Right,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct TextStyle {
+    /// Font size in pixels for screen text, in world units for world text.
+    pub size: f32,
+    pub color: [f32; 4],
+    pub align: TextAlign,
+    /// Words past this width wrap onto a new line, in the same units as `size`.
+    pub max_width: Option<f32>,
+    /// Multiplies the line height of the font.
+    pub line_spacing: f32,
+}
+
+impl Default for TextStyle {
+    fn default() -> Self {
+        Self {
+            size: 16.0,
+            color: [1.0, 1.0, 1.0, 1.0],
+            align: TextAlign::Left,
+            max_width: None,
+            line_spacing: 1.0,
+        }
+    }
+}
+
+impl TextStyle {
+    pub fn new(size: f32) -> Self {
+        Self {
+            size,
+            ..Self::default()
+        }
+    }
+
+    pub fn with_color(mut self, color: [f32; 4]) -> Self {
+        self.color = color;
+        self
+    }
+
+    pub fn with_align(mut self, align: TextAlign) -> Self {
+        self.align = align;
+        self
+    }
+
+    pub fn with_max_width(mut self, max_width: f32) -> Self {
+        self.max_width = Some(max_width);
+        self
+    }
+}
+
+/// Glyph quad relative to the text position, with y pointing down, in `TextStyle::size` units.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct GlyphQuad {
+    pub min: [f32; 2],
+    pub size: [f32; 2],
+    /// Offset and size of the glyph in the atlas texture, see `DrawCall::with_uv_rect`.
+    pub uv_rect: [f32; 4],
+}
+
+/// TTF or OTF font rasterized on demand into a glyph atlas texture.
+pub struct Font {
+    font: FontVec,
+    /// Size the glyphs are rasterized at, text drawn much bigger gets blurry.
+    pixel_size: f32,
+    atlas: GlyphAtlas,
+    pub(crate) texture_id: usize,
+}
+
+impl Font {
+    pub const ATLAS_SIZE: u32 = 1024;
+
+    pub fn new(data: Vec<u8>, pixel_size: f32, texture_id: usize) -> Option<Self> {
+        let font = FontVec::try_from_vec(data).ok()?;
+
+        Some(Self {
+            font,
+            pixel_size,
+            atlas: GlyphAtlas::new(Self::ATLAS_SIZE),
+            texture_id,
+        })
+    }
+
+    /// Width of the widest line and height of all the lines.
+    pub fn measure(&self, text: &str, style: &TextStyle) -> [f32; 2] {
+        let lines = self.lines(text, style);
+        let width = lines.iter().map(|line| line.width).fold(0.0, f32::max);
+
+        [width, lines.len() as f32 * self.line_height(style)]
+    }
+
+    /// Positions the glyphs of `text`, rasterizing the ones not in the atlas yet.
+    pub fn layout(&mut self, text: &str, style: &TextStyle) -> Vec<GlyphQuad> {
+        let lines = self.lines(text, style);
+
+        let ascent = self.font.as_scaled(PxScale::from(style.size)).ascent();
+        let line_height = self.line_height(style);
+        let scale = style.size / self.pixel_size;
+        let raster_scale = PxScale::from(self.pixel_size);
+
+        let mut quads = Vec::new();
+
+        for (index, line) in lines.iter().enumerate() {
+            let left = match style.align {
+                TextAlign::Left => 0.0,
+                TextAlign::Center => -line.width / 2.0,
+                TextAlign::Right => -line.width,
+            };
+
+            let baseline = ascent + index as f32 * line_height;
+
+            for &(id, x) in &line.glyphs {
+                // Whitespace and glyphs that don't fit in the atlas have nothing to draw.
+                let Some(glyph) = self.atlas.get(&self.font, raster_scale, id) else {
+                    continue;
+                };
+
+                quads.push(GlyphQuad {
+                    min: [left + x + glyph.offset[0] * scale, baseline + glyph.offset[1] * scale],
+                    size: [glyph.size[0] * scale, glyph.size[1] * scale],
+                    uv_rect: glyph.uv_rect,
+                });
+            }
+        }
+
+        quads
+    }
+
+    /// Atlas rows glyphs were added to since the last call and their pixels, to upload into the texture.
+    pub(crate) fn atlas_changes(&mut self) -> Option<(Range<u32>, &[u8])> {
+        let rows = self.atlas.dirty.take()?;
+        let row_bytes = (self.atlas.size * 4) as usize;
+
+        let bytes = rows.start as usize * row_bytes..rows.end as usize * row_bytes;
+
+        Some((rows, &self.atlas.pixels[bytes]))
+    }
+
+    fn line_height(&self, style: &TextStyle) -> f32 {
+        let font = self.font.as_scaled(PxScale::from(style.size));
+        (font.ascent() - font.descent() + font.line_gap()) * style.line_spacing
+    }
+
+    /// Splits `text` on new lines and wraps it between words at `style.max_width`.
+    fn lines(&self, text: &str, style: &TextStyle) -> Vec<Line> {
+        let font = self.font.as_scaled(PxScale::from(style.size));
+        let mut lines = Vec::new();
+
+        for paragraph in text.split('\n') {
+            let mut line = Line::default();
+
+            for word in paragraph.split_inclusive(' ') {
+                let mut placed = line.place(&font, word);
+
+                let too_wide = style.max_width.is_some_and(|max_width| placed.width > max_width);
+
+                // A word wider than the whole line stays on its own line.
+                if too_wide && !line.glyphs.is_empty() {
+                    lines.push(std::mem::take(&mut line));
+                    placed = line.place(&font, word);
+                }
+
+                line = placed;
+            }
+
+            lines.push(line);
+        }
+
+        lines
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+struct Line {
+    /// Glyphs and their pen position from the start of the line.
+    glyphs: Vec<(GlyphId, f32)>,
+    /// Pen position after the last glyph.
+    advance: f32,
+    /// Advance without trailing spaces, used for wrapping and alignment.
+    width: f32,
+    previous: Option<GlyphId>,
+}
+
+impl Line {
+    /// Copy of the line with `word` appended.
+    fn place(&self, font: &PxScaleFont<&FontVec>, word: &str) -> Line {
+        let mut line = self.clone();
+
+        for character in word.chars() {
+            let id = font.glyph_id(character);
+
+            if let Some(previous) = line.previous {
+                line.advance += font.kern(previous, id);
+            }
+
+            line.glyphs.push((id, line.advance));
+            line.advance += font.h_advance(id);
+            line.previous = Some(id);
+
+            if !character.is_whitespace() {
+                line.width = line.advance;
+            }
+        }
+
+        line
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+struct AtlasGlyph {
+    uv_rect: [f32; 4],
+    /// Top left corner from the pen position on the baseline, in rasterized pixels.
+    offset: [f32; 2],
+    size: [f32; 2],
+}
+
+/// RGBA atlas with white texels and the glyph coverage in alpha, packed in rows.
+struct GlyphAtlas {
+    size: u32,
+    pixels: Vec<u8>,
+    cursor: (u32, u32),
+    row_height: u32,
+    /// `None` for glyphs without an outline or that didn't fit.
+    glyphs: HashMap<GlyphId, Option<AtlasGlyph>>,
+    /// Rows changed since the last upload.
+    dirty: Option<Range<u32>>,
+}
+
+impl GlyphAtlas {
+    /// Space between glyphs so linear filtering doesn't bleed into the neighbours.
+    const PADDING: u32 = 1;
+
+    fn new(size: u32) -> Self {
+        Self {
+            size,
+            // White with zero alpha, so filtered glyph edges don't turn dark.
+            pixels: [255, 255, 255, 0].repeat((size * size) as usize),
+            cursor: (Self::PADDING, Self::PADDING),
+            row_height: 0,
+            glyphs: HashMap::new(),
+            dirty: Some(0..size),
+        }
+    }
+
+    fn get(&mut self, font: &FontVec, scale: PxScale, id: GlyphId) -> Option<AtlasGlyph> {
+        if let Some(&glyph) = self.glyphs.get(&id) {
+            return glyph;
+        }
+
+        let glyph = self.rasterize(font, scale, id);
+        self.glyphs.insert(id, glyph);
+
+        glyph
+    }
+
+    fn rasterize(&mut self, font: &FontVec, scale: PxScale, id: GlyphId) -> Option<AtlasGlyph> {
+        let outlined = font.outline_glyph(id.with_scale_and_position(scale, ab_glyph::point(0.0, 0.0)))?;
+        let bounds = outlined.px_bounds();
+
+        let (width, height) = (bounds.width() as u32, bounds.height() as u32);
+
+        if self.cursor.0 + width + Self::PADDING > self.size {
+            self.cursor = (Self::PADDING, self.cursor.1 + self.row_height);
+            self.row_height = 0;
+        }
+
+        if self.cursor.1 + height + Self::PADDING > self.size {
+            log::warn!("Glyph atlas is full, glyph {} is not drawn", id.0);
+            return None;
+        }
+
+        let (left, top) = self.cursor;
+
+        outlined.draw(|x, y, coverage| {
+            let index = (((top + y) * self.size + left + x) * 4 + 3) as usize;
+            self.pixels[index] = (coverage * 255.0) as u8;
+        });
+
+        self.cursor.0 += width + Self::PADDING;
+        self.row_height = self.row_height.max(height + Self::PADDING);
+
+        self.dirty = Some(match self.dirty.take() {
+            Some(dirty) => dirty.start.min(top)..dirty.end.max(top + height),
+            None => top..top + height,
+        });
+
+        let size = self.size as f32;
+
+        Some(AtlasGlyph {
+            uv_rect: [left as f32 / size, top as f32 / size, width as f32 / size, height as f32 / size],
+            offset: [bounds.min.x, bounds.min.y],
+            size: [width as f32, height as f32],
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn font() -> Font {
+        let data = include_bytes!("../../resources/Cantarell-Regular.ttf").to_vec();
+        Font::new(data, 32.0, 0).unwrap()
+    }
+
+    fn widths(font: &Font, text: &str, style: &TextStyle) -> Vec<f32> {
+        font.lines(text, style).iter().map(|line| line.width).collect()
+    }
+
+    #[test]
+    fn new_lines_start_lines() {
+        let font = font();
+
+        assert_eq!(font.lines("a\nb\n\nc", &TextStyle::default()).len(), 4);
+    }
+
+    #[test]
+    fn trailing_spaces_are_not_counted() {
+        let font = font();
+        let style = TextStyle::default();
+
+        assert_eq!(widths(&font, "farm  ", &style), widths(&font, "farm", &style));
+    }
+
+    #[test]
+    fn wraps_between_words() {
+        let font = font();
+        let style = TextStyle::default();
+        let [first, _] = font.measure("plant the", &style);
+
+        let wrapped = widths(&font, "plant the seeds", &style.with_max_width(first + 1.0));
+
+        assert_eq!(wrapped, [first, widths(&font, "seeds", &style)[0]]);
+    }
+
+    #[test]
+    fn words_wider_than_the_line_stay_on_their_own_line() {
+        let font = font();
+        let style = TextStyle::default().with_max_width(1.0);
+
+        let lines = font.lines("a watering can", &style);
+
+        assert_eq!(lines.len(), 3);
+        assert!(lines.iter().all(|line| !line.glyphs.is_empty()));
+    }
+
+    #[test]
+    fn measure_counts_every_line() {
+        let font = font();
+        let style = TextStyle::default();
+
+        let [_, one] = font.measure("hay", &style);
+        let [_, three] = font.measure("hay\nhay\nhay", &style);
+
+        assert!((three - 3.0 * one).abs() < 1e-3);
+    }
+
+    #[test]
+    fn alignment_moves_lines_by_their_width() {
+        let mut font = font();
+        let style = TextStyle::default();
+        let [width, _] = font.measure("barn", &style);
+
+        let left = font.layout("barn", &style)[0].min[0];
+        let center = font.layout("barn", &style.with_align(TextAlign::Center))[0].min[0];
+        let right = font.layout("barn", &style.with_align(TextAlign::Right))[0].min[0];
+
+        assert!((left - center - width / 2.0).abs() < 1e-3);
+        assert!((left - right - width).abs() < 1e-3);
+    }
+
+    #[test]
+    fn atlas_uploads_only_new_rows() {
+        let mut font = font();
+
+        let (rows, pixels) = font.atlas_changes().unwrap();
+        assert_eq!(rows, 0..Font::ATLAS_SIZE);
+        assert_eq!(pixels.len(), (Font::ATLAS_SIZE * Font::ATLAS_SIZE * 4) as usize);
+        assert!(font.atlas_changes().is_none());
+
+        font.layout("o", &TextStyle::default());
+
+        let (rows, pixels) = font.atlas_changes().unwrap();
+        assert!(rows.start >= GlyphAtlas::PADDING && rows.len() < 64);
+        assert_eq!(pixels.len(), rows.len() * Font::ATLAS_SIZE as usize * 4);
+
+        // Glyphs already in the atlas don't upload anything.
+        font.layout("o", &TextStyle::default());
+        assert!(font.atlas_changes().is_none());
+    }
+}
@@ -1,32 +1,99 @@
+use std::ops::Range;
+
+#[allow(unused)]
 pub struct Texture {
-    _texture: wgpu::Texture,
+    texture: wgpu::Texture,
     pub layout: wgpu::BindGroupLayout,
     pub bind_group: wgpu::BindGroup,
 }
 
 impl Texture {
     pub fn new(bytes: &[u8], name: &str, device: &wgpu::Device, queue: &wgpu::Queue) -> Self {
-        let texture = Self::create_texture(bytes, name, device, queue);
+        Self::try_new(bytes, name, device, queue).expect("Can't decode texture")
+    }
+
+    /// Like `new`, but returns `None` when the bytes aren't a valid image.
+    pub fn try_new(bytes: &[u8], name: &str, device: &wgpu::Device, queue: &wgpu::Queue) -> Option<Self> {
+        let texture = Self::create_texture(bytes, name, device, queue)?;
+        Some(Self::from_texture(texture, device))
+    }
+
+    /// Wraps a texture created elsewhere, e.g. the color texture of a render target.
+    pub fn from_texture(texture: wgpu::Texture, device: &wgpu::Device) -> Self {
         let layout = Self::create_bind_group_layout(device);
         let bind_group = Self::create_bind_group(device, &texture, &layout);
 
         Self {
-            _texture: texture,
+            texture,
             layout,
             bind_group,
         }
     }
 
-    fn create_texture(bytes: &[u8], name: &str, device: &wgpu::Device, queue: &wgpu::Queue) -> wgpu::Texture {
+    /// Texture from raw RGBA8 pixels, e.g. generated ones like the glyph atlas.
+    pub fn from_rgba(
+        pixels: &[u8],
+        width: u32,
+        height: u32,
+        name: &str,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+    ) -> Self {
+        let texture = Self::create_rgba_texture(pixels, width, height, name, device, queue);
+        Self::from_texture(texture, device)
+    }
+
+    /// Width and height in pixels.
+    pub fn size(&self) -> (u32, u32) {
+        (self.texture.width(), self.texture.height())
+    }
+
+    /// Replaces the pixels of `rows`, `pixels` holds those whole rows of the texture.
+    pub fn write_rows(&self, queue: &wgpu::Queue, rows: Range<u32>, pixels: &[u8]) {
+        let width = self.texture.size().width;
+
+        queue.write_texture(
+            wgpu::ImageCopyTexture {
+                texture: &self.texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d { x: 0, y: rows.start, z: 0 },
+                aspect: wgpu::TextureAspect::All,
+            },
+            pixels,
+            wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: Some(4 * width),
+                rows_per_image: Some(rows.len() as u32),
+            },
+            wgpu::Extent3d {
+                width,
+                height: rows.len() as u32,
+                depth_or_array_layers: 1,
+            },
+        );
+    }
+
+    fn create_texture(bytes: &[u8], name: &str, device: &wgpu::Device, queue: &wgpu::Queue) -> Option<wgpu::Texture> {
         let diffuse_bytes = bytes;
-        let diffuse_image = image::load_from_memory(diffuse_bytes).unwrap();
+        let diffuse_image = image::load_from_memory(diffuse_bytes).ok()?;
         let diffuse_rgba = diffuse_image.to_rgba8();
 
-        let dimensions = diffuse_rgba.dimensions();
+        let (width, height) = diffuse_rgba.dimensions();
+
+        Some(Self::create_rgba_texture(&diffuse_rgba, width, height, name, device, queue))
+    }
 
+    fn create_rgba_texture(
+        pixels: &[u8],
+        width: u32,
+        height: u32,
+        name: &str,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+    ) -> wgpu::Texture {
         let texture_size = wgpu::Extent3d {
-            width: dimensions.0,
-            height: dimensions.1,
+            width,
+            height,
             depth_or_array_layers: 1,
         };
 
@@ -52,11 +119,11 @@ impl Texture {
                 origin: wgpu::Origin3d::ZERO,
                 aspect: wgpu::TextureAspect::All,
             },
-            &diffuse_rgba,
+            pixels,
             wgpu::ImageDataLayout {
                 offset: 0,
-                bytes_per_row: Some(4 * dimensions.0),
-                rows_per_image: Some(dimensions.1),
+                bytes_per_row: Some(4 * width),
+                rows_per_image: Some(height),
             },
             texture_size,
         );
@@ -118,7 +185,7 @@ impl Texture {
         });
 
         let desc = wgpu::BindGroupDescriptor {
-            layout: &layout,
+            layout,
             entries: &[
                 wgpu::BindGroupEntry {
                     binding: 0,
@@ -140,7 +207,8 @@ impl Texture {
 pub struct DepthTexture {
     texture: wgpu::Texture,
     pub view: wgpu::TextureView,
-    sampler: wgpu::Sampler,
+    /// Comparison sampler, used when the depth is read back as a shadow map.
+    pub sampler: wgpu::Sampler,
 }
 
 impl DepthTexture {
@@ -149,11 +217,22 @@ impl DepthTexture {
     pub fn new(
         device: &wgpu::Device,
         config: &wgpu::SurfaceConfiguration,
+        sample_count: u32,
+        label: &str,
+    ) -> Self {
+        Self::with_size(device, config.width, config.height, sample_count, label)
+    }
+
+    pub fn with_size(
+        device: &wgpu::Device,
+        width: u32,
+        height: u32,
+        sample_count: u32,
         label: &str,
     ) -> Self {
         let size = wgpu::Extent3d {
-            width: config.width,
-            height: config.height,
+            width,
+            height,
             depth_or_array_layers: 1,
         };
 
@@ -161,7 +240,7 @@ impl DepthTexture {
             label: Some(label),
             size,
             mip_level_count: 1,
-            sample_count: 1,
+            sample_count,
             dimension: wgpu::TextureDimension::D2,
             format: Self::DEPTH_FORMAT,
             usage: wgpu::TextureUsages::RENDER_ATTACHMENT |
@@ -199,4 +278,103 @@ impl DepthTexture {
             sampler,
         }
     }
-}
\ No newline at end of file
+}
+
+/// Multisampled color target that gets resolved into the surface texture.
+pub struct MultisampledTexture {
+    _texture: wgpu::Texture,
+    pub view: wgpu::TextureView,
+}
+
+impl MultisampledTexture {
+    pub fn new(
+        device: &wgpu::Device,
+        config: &wgpu::SurfaceConfiguration,
+        sample_count: u32,
+        label: &str,
+    ) -> Self {
+        let desc = wgpu::TextureDescriptor {
+            label: Some(label),
+            size: wgpu::Extent3d {
+                width: config.width,
+                height: config.height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count,
+            dimension: wgpu::TextureDimension::D2,
+            format: config.format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            view_formats: &[],
+        };
+
+        let texture = device.create_texture(&desc);
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+
+        Self {
+            _texture: texture,
+            view,
+        }
+    }
+}
+
+/// Offscreen color target a camera can draw into, sampled afterwards as a regular texture.
+pub struct RenderTarget {
+    pub view: wgpu::TextureView,
+    pub depth_texture: DepthTexture,
+    pub msaa_texture: Option<MultisampledTexture>,
+    pub config: wgpu::SurfaceConfiguration,
+}
+
+impl RenderTarget {
+    /// Returns the target together with the texture to register for sampling.
+    /// `config` must use the format of the main render pipeline so it can draw into it.
+    pub fn new(
+        device: &wgpu::Device,
+        config: &wgpu::SurfaceConfiguration,
+        width: u32,
+        height: u32,
+        sample_count: u32,
+    ) -> (Self, Texture) {
+        let config = wgpu::SurfaceConfiguration {
+            width,
+            height,
+            ..config.clone()
+        };
+
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Render target"),
+            size: wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: config.format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        });
+
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+
+        let mut target = Self {
+            view,
+            depth_texture: DepthTexture::new(device, &config, sample_count, "render target depth"),
+            msaa_texture: None,
+            config,
+        };
+
+        target.set_sample_count(device, sample_count);
+
+        (target, Texture::from_texture(texture, device))
+    }
+
+    pub fn set_sample_count(&mut self, device: &wgpu::Device, sample_count: u32) {
+        self.depth_texture = DepthTexture::new(device, &self.config, sample_count, "render target depth");
+        self.msaa_texture = (sample_count > 1).then(|| {
+            MultisampledTexture::new(device, &self.config, sample_count, "render target msaa")
+        });
+    }
+}
@@ -0,0 +1,396 @@
+use std::f32::consts::PI;
+use cgmath::InnerSpace;
+use crate::app::light::Lights;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Season {
+    Spring,
+    Summer,
+    Autumn,
+    Winter,
+}
+
+impl Season {
+    pub const ALL: [Season; 4] = [Season::Spring, Season::Summer, Season::Autumn, Season::Winter];
+}
+
+/// In-game calendar and time of day.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct GameClock {
+    /// Hours since midnight, from 0 up to 24.
+    hour: f32,
+    /// Days since the start of the game, starting at 0.
+    day: u32,
+    /// Real seconds it takes for one in-game hour to pass.
+    pub seconds_per_hour: f32,
+    pub days_per_season: u32,
+    pub paused: bool,
+}
+
+impl Default for GameClock {
+    fn default() -> Self {
+        Self {
+            hour: 6.0,
+            day: 0,
+            seconds_per_hour: 30.0,
+            days_per_season: 28,
+            paused: false,
+        }
+    }
+}
+
+impl GameClock {
+    /// Moves the clock forward by `dt` real seconds. Returns how many new hours have started,
+    /// more than one when a long frame or a fast clock skips over some.
+    pub fn advance(&mut self, dt: f32) -> u32 {
+        if self.paused || self.seconds_per_hour <= 0.0 {
+            return 0;
+        }
+
+        let previous = self.day * 24 + self.hour();
+
+        self.hour += dt / self.seconds_per_hour;
+
+        while self.hour >= 24.0 {
+            self.hour -= 24.0;
+            self.day += 1;
+        }
+
+        self.day * 24 + self.hour() - previous
+    }
+
+    /// The clock at the start of the hour `hours` after the current one.
+    pub fn hours_later(&self, hours: u32) -> GameClock {
+        let hour = self.hour() + hours;
+
+        GameClock {
+            hour: (hour % 24) as f32,
+            day: self.day + hour / 24,
+            ..*self
+        }
+    }
+
+    /// Fractional hour since midnight, e.g. 13.5 is half past one in the afternoon.
+    pub fn time(&self) -> f32 {
+        self.hour
+    }
+
+    pub fn hour(&self) -> u32 {
+        self.hour as u32
+    }
+
+    pub fn minute(&self) -> u32 {
+        (self.hour.fract() * 60.0) as u32
+    }
+
+    pub fn day(&self) -> u32 {
+        self.day
+    }
+
+    pub fn set_time(&mut self, hour: f32) {
+        self.hour = hour.rem_euclid(24.0);
+    }
+
+    /// Days since the start of the game, e.g. from a saved game. The season and year follow from it.
+    pub fn set_day(&mut self, day: u32) {
+        self.day = day;
+    }
+
+    /// Jumps to `hour` of the next day, e.g. after going to bed.
+    pub fn skip_to_next_day(&mut self, hour: f32) {
+        self.day += 1;
+        self.set_time(hour);
+    }
+
+    pub fn season(&self) -> Season {
+        let index = self.day / self.days_per_season.max(1) % 4;
+        Season::ALL[index as usize]
+    }
+
+    /// Day of the current season, starting at 1.
+    pub fn day_of_season(&self) -> u32 {
+        self.day % self.days_per_season.max(1) + 1
+    }
+
+    pub fn year(&self) -> u32 {
+        self.day / (self.days_per_season.max(1) * 4) + 1
+    }
+
+    /// Whether the current time is in `[from, to)`, wrapping past midnight when `to < from`.
+    pub fn is_between(&self, from: f32, to: f32) -> bool {
+        if from <= to {
+            self.hour >= from && self.hour < to
+        } else {
+            self.hour >= from || self.hour < to
+        }
+    }
+}
+
+/// Colors of the sky and the light at a given hour.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct SkyKey {
+    pub hour: f32,
+    pub sky: [f32; 3],
+    pub ambient: [f32; 3],
+    /// Color of the sun during the day and of the moon at night.
+    pub sun_color: [f32; 3],
+    pub sun_intensity: f32,
+}
+
+impl SkyKey {
+    fn lerp(&self, other: &SkyKey, t: f32) -> SkyKey {
+        let mix = |a: [f32; 3], b: [f32; 3]| [0, 1, 2].map(|i| a[i] + (b[i] - a[i]) * t);
+
+        SkyKey {
+            hour: self.hour + (other.hour - self.hour) * t,
+            sky: mix(self.sky, other.sky),
+            ambient: mix(self.ambient, other.ambient),
+            sun_color: mix(self.sun_color, other.sun_color),
+            sun_intensity: self.sun_intensity + (other.sun_intensity - self.sun_intensity) * t,
+        }
+    }
+}
+
+/// Keys over the 24 hours, interpolated linearly and wrapping around midnight.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SkyGradient {
+    keys: Vec<SkyKey>,
+}
+
+impl Default for SkyGradient {
+    fn default() -> Self {
+        let key = |hour, sky, ambient, sun_color, sun_intensity| SkyKey {
+            hour,
+            sky,
+            ambient,
+            sun_color,
+            sun_intensity,
+        };
+
+        Self::new(vec![
+            key(0.0, [0.01, 0.01, 0.04], [0.05, 0.06, 0.12], [0.5, 0.6, 1.0], 0.15),
+            key(5.0, [0.03, 0.03, 0.08], [0.07, 0.07, 0.14], [0.5, 0.6, 1.0], 0.1),
+            key(6.5, [0.8, 0.45, 0.3], [0.3, 0.25, 0.25], [1.0, 0.6, 0.4], 0.5),
+            key(9.0, [0.45, 0.7, 0.95], [0.35, 0.35, 0.38], [1.0, 0.95, 0.85], 0.8),
+            key(16.0, [0.45, 0.7, 0.95], [0.35, 0.35, 0.38], [1.0, 0.95, 0.85], 0.8),
+            key(18.5, [0.85, 0.4, 0.25], [0.3, 0.22, 0.22], [1.0, 0.5, 0.3], 0.5),
+            key(20.0, [0.05, 0.04, 0.12], [0.08, 0.08, 0.15], [0.5, 0.6, 1.0], 0.1),
+        ])
+    }
+}
+
+impl SkyGradient {
+    pub fn new(mut keys: Vec<SkyKey>) -> Self {
+        assert!(!keys.is_empty(), "sky gradient needs at least one key");
+
+        keys.sort_by(|a, b| a.hour.total_cmp(&b.hour));
+        Self { keys }
+    }
+
+    pub fn sample(&self, hour: f32) -> SkyKey {
+        let hour = hour.rem_euclid(24.0);
+
+        let next = self.keys.iter()
+            .position(|key| key.hour > hour)
+            .unwrap_or(0);
+
+        let previous = if next == 0 { self.keys.len() - 1 } else { next - 1 };
+
+        let (from, to) = (&self.keys[previous], &self.keys[next]);
+
+        // Hours between the keys, going past midnight when the next key is earlier in the day.
+        let span = (to.hour - from.hour).rem_euclid(24.0);
+        let elapsed = (hour - from.hour).rem_euclid(24.0);
+
+        let t = if span > 0.0 { elapsed / span } else { 0.0 };
+        from.lerp(to, t)
+    }
+}
+
+/// Drives the sky color, ambient light and sun from the game clock.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DayNight {
+    pub clock: GameClock,
+    pub gradient: SkyGradient,
+    /// Hours the sun rises and sets, the moon takes over in between.
+    pub sunrise: f32,
+    pub sunset: f32,
+    /// When disabled the lights are left as the game sets them and the sky stays black.
+    pub enabled: bool,
+}
+
+impl Default for DayNight {
+    fn default() -> Self {
+        Self {
+            clock: GameClock::default(),
+            gradient: SkyGradient::default(),
+            sunrise: 6.0,
+            sunset: 19.0,
+            enabled: true,
+        }
+    }
+}
+
+impl DayNight {
+    pub fn sky_color(&self) -> wgpu::Color {
+        if !self.enabled {
+            return wgpu::Color::BLACK;
+        }
+
+        let [r, g, b] = self.gradient.sample(self.clock.time()).sky;
+
+        wgpu::Color {
+            r: r as f64,
+            g: g as f64,
+            b: b as f64,
+            a: 1.0,
+        }
+    }
+
+    /// Direction the sun light travels in, or the moon light at night.
+    pub fn sun_direction(&self) -> cgmath::Vector3<f32> {
+        let day_length = (self.sunset - self.sunrise).rem_euclid(24.0);
+        let since_sunrise = (self.clock.time() - self.sunrise).rem_euclid(24.0);
+
+        let (progress, length) = if since_sunrise < day_length {
+            (since_sunrise, day_length)
+        } else {
+            (since_sunrise - day_length, 24.0 - day_length)
+        };
+
+        // Rises in the east (+x), sets in the west, tilted south so noon shadows aren't straight down.
+        let angle = progress / length * PI;
+        let position = cgmath::Vector3::new(angle.cos(), angle.sin().max(0.1), 0.4);
+
+        -position.normalize()
+    }
+
+    pub fn apply(&self, lights: &mut Lights) {
+        if !self.enabled {
+            return;
+        }
+
+        let key = self.gradient.sample(self.clock.time());
+
+        lights.ambient = key.ambient;
+        lights.sun.color = key.sun_color;
+        lights.sun.intensity = key.sun_intensity;
+        lights.sun.direction = self.sun_direction();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn clock_at(hour: f32) -> GameClock {
+        let mut clock = GameClock {
+            seconds_per_hour: 10.0,
+            ..GameClock::default()
+        };
+
+        clock.set_time(hour);
+        clock
+    }
+
+    #[test]
+    fn advance_counts_started_hours() {
+        let mut clock = clock_at(6.0);
+
+        assert_eq!(clock.advance(5.0), 0);
+        assert_eq!((clock.hour(), clock.minute()), (6, 30));
+
+        assert_eq!(clock.advance(5.0), 1);
+        assert_eq!(clock.hour(), 7);
+
+        // A long frame crosses several hours at once.
+        assert_eq!(clock.advance(35.0), 3);
+        assert_eq!((clock.hour(), clock.minute()), (10, 30));
+    }
+
+    #[test]
+    fn advance_rolls_over_to_the_next_day() {
+        let mut clock = clock_at(23.5);
+
+        assert_eq!(clock.advance(10.0), 1);
+        assert_eq!((clock.day(), clock.hour(), clock.minute()), (1, 0, 30));
+
+        assert_eq!(clock.advance(24.0 * 10.0 * 2.0), 48);
+        assert_eq!((clock.day(), clock.hour()), (3, 0));
+    }
+
+    #[test]
+    fn paused_clock_does_not_advance() {
+        let mut clock = clock_at(6.0);
+        clock.paused = true;
+
+        assert_eq!(clock.advance(100.0), 0);
+        assert_eq!(clock.time(), 6.0);
+    }
+
+    #[test]
+    fn hours_later_starts_each_hour() {
+        let clock = clock_at(22.75);
+
+        let later = clock.hours_later(1);
+        assert_eq!((later.day(), later.time()), (0, 23.0));
+
+        let later = clock.hours_later(3);
+        assert_eq!((later.day(), later.time()), (1, 1.0));
+    }
+
+    #[test]
+    fn calendar_follows_the_day() {
+        let mut clock = GameClock::default();
+
+        clock.set_day(27);
+        assert_eq!((clock.season(), clock.day_of_season(), clock.year()), (Season::Spring, 28, 1));
+
+        clock.skip_to_next_day(6.0);
+        assert_eq!((clock.season(), clock.day_of_season()), (Season::Summer, 1));
+
+        clock.set_day(28 * 4 + 2 * 28);
+        assert_eq!((clock.season(), clock.day_of_season(), clock.year()), (Season::Autumn, 1, 2));
+    }
+
+    #[test]
+    fn is_between_wraps_past_midnight() {
+        assert!(clock_at(9.0).is_between(8.0, 17.0));
+        assert!(!clock_at(17.0).is_between(8.0, 17.0));
+        assert!(clock_at(23.0).is_between(22.0, 6.0));
+        assert!(clock_at(2.0).is_between(22.0, 6.0));
+        assert!(!clock_at(12.0).is_between(22.0, 6.0));
+    }
+
+    fn key(hour: f32, value: f32) -> SkyKey {
+        SkyKey {
+            hour,
+            sky: [value; 3],
+            ambient: [value; 3],
+            sun_color: [value; 3],
+            sun_intensity: value,
+        }
+    }
+
+    #[test]
+    fn gradient_interpolates_between_keys() {
+        let gradient = SkyGradient::new(vec![key(18.0, 0.0), key(6.0, 1.0)]);
+
+        assert_eq!(gradient.sample(6.0).sun_intensity, 1.0);
+        assert_eq!(gradient.sample(12.0).sun_intensity, 0.5);
+        assert_eq!(gradient.sample(18.0).sun_intensity, 0.0);
+        assert_eq!(gradient.sample(9.0).sky, [0.75; 3]);
+    }
+
+    #[test]
+    fn gradient_wraps_around_midnight() {
+        let gradient = SkyGradient::new(vec![key(6.0, 1.0), key(18.0, 0.0)]);
+
+        assert_eq!(gradient.sample(0.0).sun_intensity, 0.5);
+        assert_eq!(gradient.sample(-3.0).sun_intensity, 0.25);
+        assert_eq!(gradient.sample(30.0).sun_intensity, 1.0);
+
+        let single = SkyGradient::new(vec![key(12.0, 0.3)]);
+        assert_eq!(single.sample(3.0).sun_intensity, 0.3);
+    }
+}
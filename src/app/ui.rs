@@ -0,0 +1,738 @@
+use std::collections::HashMap;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use winit::event::MouseButton;
+use winit::keyboard::KeyCode;
+use crate::app::context::Renderer;
+use crate::app::geometry::Rect;
+use crate::app::text::{TextAlign, TextStyle};
+
+/// Point of the parent rectangle a widget is attached to, so layouts follow window resizes.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub enum Anchor {
+    TopLeft,
+    Top,
+    TopRight,
+    Left,
+    #[default]
+    Center,
+    Right,
+    BottomLeft,
+    Bottom,
+    BottomRight,
+}
+
+impl Anchor {
+    /// Rectangle of `width` by `height` attached to `parent`, kept `margin` pixels away from the anchored edges.
+    pub fn place(self, parent: &Rect, width: f32, height: f32, margin: [f32; 2]) -> Rect {
+        let (horizontal, vertical) = self.factors();
+
+        Rect::new(
+            parent.x + (parent.width - width) * horizontal + margin[0] * (1.0 - 2.0 * horizontal),
+            parent.y + (parent.height - height) * vertical + margin[1] * (1.0 - 2.0 * vertical),
+            width,
+            height,
+        )
+    }
+
+    /// Horizontal and vertical position in the parent, from 0 at the top left to 1 at the bottom right.
+    fn factors(self) -> (f32, f32) {
+        match self {
+            Anchor::TopLeft => (0.0, 0.0),
+            Anchor::Top => (0.5, 0.0),
+            Anchor::TopRight => (1.0, 0.0),
+            Anchor::Left => (0.0, 0.5),
+            Anchor::Center => (0.5, 0.5),
+            Anchor::Right => (1.0, 0.5),
+            Anchor::BottomLeft => (0.0, 1.0),
+            Anchor::Bottom => (0.5, 1.0),
+            Anchor::BottomRight => (1.0, 1.0),
+        }
+    }
+}
+
+/// Focus navigation, read from the keyboard every frame. Gamepads and other devices
+/// queue them with `Ui::navigate`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum NavAction {
+    Up,
+    Down,
+    Left,
+    Right,
+    /// Next widget in drawing order, wrapping around.
+    Next,
+    Previous,
+    /// Clicks the focused widget.
+    Activate,
+    /// Reported by `UiFrame::back_pressed`, usually closes the menu.
+    Back,
+}
+
+impl NavAction {
+    fn from_keyboard(key: KeyCode, shift: bool) -> Option<Self> {
+        match key {
+            KeyCode::ArrowUp => Some(NavAction::Up),
+            KeyCode::ArrowDown => Some(NavAction::Down),
+            KeyCode::ArrowLeft => Some(NavAction::Left),
+            KeyCode::ArrowRight => Some(NavAction::Right),
+            KeyCode::Tab if shift => Some(NavAction::Previous),
+            KeyCode::Tab => Some(NavAction::Next),
+            KeyCode::Enter | KeyCode::NumpadEnter | KeyCode::Space => Some(NavAction::Activate),
+            KeyCode::Escape | KeyCode::Backspace => Some(NavAction::Back),
+            _ => None,
+        }
+    }
+
+    const KEYS: [KeyCode; 10] = [
+        KeyCode::ArrowUp,
+        KeyCode::ArrowDown,
+        KeyCode::ArrowLeft,
+        KeyCode::ArrowRight,
+        KeyCode::Tab,
+        KeyCode::Enter,
+        KeyCode::NumpadEnter,
+        KeyCode::Space,
+        KeyCode::Escape,
+        KeyCode::Backspace,
+    ];
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct UiTheme {
+    /// Font id returned by `Renderer::add_font`.
+    pub font: usize,
+    pub text_size: f32,
+    pub text_color: [f32; 4],
+    pub panel_color: [f32; 4],
+    pub border_color: [f32; 4],
+    pub button_color: [f32; 4],
+    pub hover_color: [f32; 4],
+    pub focus_color: [f32; 4],
+    pub bar_background: [f32; 4],
+    pub tooltip_color: [f32; 4],
+    pub border_width: f32,
+    /// Space between the border of a widget and its content.
+    pub padding: f32,
+    /// Space between list items and grid slots.
+    pub spacing: f32,
+}
+
+impl UiTheme {
+    pub fn new(font: usize) -> Self {
+        Self {
+            font,
+            text_size: 18.0,
+            text_color: [1.0, 0.96, 0.88, 1.0],
+            panel_color: [0.18, 0.12, 0.08, 0.9],
+            border_color: [0.45, 0.32, 0.2, 1.0],
+            button_color: [0.32, 0.22, 0.14, 1.0],
+            hover_color: [0.42, 0.3, 0.19, 1.0],
+            focus_color: [1.0, 0.85, 0.35, 1.0],
+            bar_background: [0.08, 0.06, 0.04, 0.9],
+            tooltip_color: [0.1, 0.08, 0.06, 0.95],
+            border_width: 2.0,
+            padding: 6.0,
+            spacing: 4.0,
+        }
+    }
+}
+
+/// What happened to a widget this frame.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct Response {
+    pub rect: Rect,
+    pub hovered: bool,
+    pub focused: bool,
+    /// Clicked with the mouse or activated while focused.
+    pub clicked: bool,
+}
+
+impl Response {
+    /// Hovered or focused, e.g. to show a tooltip.
+    pub fn is_active(&self) -> bool {
+        self.hovered || self.focused
+    }
+}
+
+/// Item drawn in an inventory, shop or crafting slot.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct SlotItem {
+    pub texture_id: usize,
+    pub uv_rect: [f32; 4],
+    /// Drawn in the corner when above 1.
+    pub count: u32,
+}
+
+impl SlotItem {
+    pub fn new(texture_id: usize) -> Self {
+        Self {
+            texture_id,
+            uv_rect: [0.0, 0.0, 1.0, 1.0],
+            count: 1,
+        }
+    }
+
+    pub fn with_uv_rect(mut self, uv_rect: [f32; 4]) -> Self {
+        self.uv_rect = uv_rect;
+        self
+    }
+
+    pub fn with_count(mut self, count: u32) -> Self {
+        self.count = count;
+        self
+    }
+}
+
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct SlotGridResponse {
+    pub clicked: Option<usize>,
+    /// Hovered or focused slot and its rectangle.
+    pub active: Option<(usize, Rect)>,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+struct WidgetId(u64);
+
+impl WidgetId {
+    fn new(parent: u64, key: impl Hash) -> Self {
+        let mut hasher = DefaultHasher::new();
+        parent.hash(&mut hasher);
+        key.hash(&mut hasher);
+
+        Self(hasher.finish())
+    }
+}
+
+/// Immediate-mode UI: widgets are declared every frame through `begin`,
+/// while focus and scroll positions are kept here between frames.
+pub struct Ui {
+    pub theme: UiTheme,
+    focus: Option<WidgetId>,
+    /// Focusable widgets of the previous frame in drawing order, navigation moves between them.
+    focusables: Vec<(WidgetId, Rect)>,
+    scroll_offsets: HashMap<WidgetId, f32>,
+    actions: Vec<NavAction>,
+}
+
+impl Ui {
+    pub fn new(theme: UiTheme) -> Self {
+        Self {
+            theme,
+            focus: None,
+            focusables: Vec::new(),
+            scroll_offsets: HashMap::new(),
+            actions: Vec::new(),
+        }
+    }
+
+    /// Queues a navigation action for the next `begin`, e.g. from a gamepad.
+    pub fn navigate(&mut self, action: NavAction) {
+        self.actions.push(action);
+    }
+
+    /// Nothing is focused until the next navigation action, which focuses the first widget.
+    pub fn clear_focus(&mut self) {
+        self.focus = None;
+    }
+
+    /// Starts declaring the widgets of this frame, finish with `UiFrame::end`.
+    pub fn begin<'f, 'a>(&'f mut self, renderer: &'f mut Renderer<'a>) -> UiFrame<'f, 'a> {
+        let input = renderer.input();
+        let shift = input.is_key_down(KeyCode::ShiftLeft) || input.is_key_down(KeyCode::ShiftRight);
+
+        let keyboard_actions = NavAction::KEYS.iter()
+            .filter(|&&key| input.is_key_pressed(key))
+            .filter_map(|&key| NavAction::from_keyboard(key, shift));
+
+        let mut actions = std::mem::take(&mut self.actions);
+        actions.extend(keyboard_actions);
+
+        let size = input.window_size();
+        let cursor = input.cursor();
+        let clicked = input.is_mouse_pressed(MouseButton::Left);
+        let scroll = input.scroll();
+
+        let mut navigated = false;
+
+        for &action in &actions {
+            navigated |= self.move_focus(action);
+        }
+
+        UiFrame {
+            screen: Rect::new(0.0, 0.0, size.width as f32, size.height as f32),
+            cursor,
+            clicked,
+            scroll,
+            activate: actions.contains(&NavAction::Activate),
+            back: actions.contains(&NavAction::Back),
+            navigated,
+            focusables: Vec::new(),
+            clip: None,
+            id_stack: vec![0],
+            tooltip: None,
+            ui: self,
+            renderer,
+        }
+    }
+
+    /// Returns `true` if the focus moved.
+    fn move_focus(&mut self, action: NavAction) -> bool {
+        if self.focusables.is_empty() {
+            return false;
+        }
+
+        let current = self.focus
+            .and_then(|focus| self.focusables.iter().position(|&(id, _)| id == focus));
+
+        let Some(current) = current else {
+            let moves = !matches!(action, NavAction::Activate | NavAction::Back);
+
+            if moves {
+                self.focus = Some(self.focusables[0].0);
+            }
+
+            return moves;
+        };
+
+        let count = self.focusables.len();
+
+        let direction = match action {
+            NavAction::Next => {
+                self.focus = Some(self.focusables[(current + 1) % count].0);
+                return true;
+            },
+            NavAction::Previous => {
+                self.focus = Some(self.focusables[(current + count - 1) % count].0);
+                return true;
+            },
+            NavAction::Up => cgmath::Vector2::new(0.0, -1.0),
+            NavAction::Down => cgmath::Vector2::new(0.0, 1.0),
+            NavAction::Left => cgmath::Vector2::new(-1.0, 0.0),
+            NavAction::Right => cgmath::Vector2::new(1.0, 0.0),
+            NavAction::Activate | NavAction::Back => return false,
+        };
+
+        let from = self.focusables[current].1.center();
+
+        // Closest widget in the direction, sideways distance counts double so grids move in straight lines.
+        let target = self.focusables.iter()
+            .filter_map(|&(id, rect)| {
+                let offset = rect.center() - from;
+                let along = offset.x * direction.x + offset.y * direction.y;
+                let sideways = (offset.x * direction.y - offset.y * direction.x).abs();
+
+                (along > 0.5).then_some((id, along + sideways * 2.0))
+            })
+            .min_by(|a, b| a.1.total_cmp(&b.1));
+
+        match target {
+            Some((id, _)) => {
+                self.focus = Some(id);
+                true
+            },
+            None => false,
+        }
+    }
+}
+
+/// Widgets declared during one frame, drawn into the overlay of the renderer.
+pub struct UiFrame<'f, 'a> {
+    ui: &'f mut Ui,
+    renderer: &'f mut Renderer<'a>,
+    screen: Rect,
+    cursor: Option<cgmath::Point2<f32>>,
+    clicked: bool,
+    scroll: f32,
+    activate: bool,
+    back: bool,
+    /// The focus moved this frame, scroll lists then bring the focused item into view.
+    navigated: bool,
+    focusables: Vec<(WidgetId, Rect)>,
+    clip: Option<Rect>,
+    id_stack: Vec<u64>,
+    tooltip: Option<(Rect, String)>,
+}
+
+impl<'f, 'a> UiFrame<'f, 'a> {
+    /// The whole window, the parent of top level anchors.
+    pub fn screen(&self) -> Rect {
+        self.screen
+    }
+
+    /// Shorthand for `Anchor::place` on the window.
+    pub fn anchored(&self, anchor: Anchor, width: f32, height: f32, margin: [f32; 2]) -> Rect {
+        anchor.place(&self.screen, width, height, margin)
+    }
+
+    pub fn theme(&self) -> &UiTheme {
+        &self.ui.theme
+    }
+
+    pub fn back_pressed(&self) -> bool {
+        self.back
+    }
+
+    /// For drawing custom content between widgets.
+    pub fn renderer(&mut self) -> &mut Renderer<'a> {
+        &mut *self.renderer
+    }
+
+    /// Makes the ids of the following widgets unique, e.g. for repeated buttons in a loop.
+    pub fn push_id(&mut self, key: impl Hash) {
+        let parent = *self.id_stack.last().unwrap_or(&0);
+        self.id_stack.push(WidgetId::new(parent, key).0);
+    }
+
+    pub fn pop_id(&mut self) {
+        if self.id_stack.len() > 1 {
+            self.id_stack.pop();
+        }
+    }
+
+    /// Background with a border, for windows and menus.
+    pub fn panel(&mut self, rect: Rect) {
+        let theme = self.ui.theme;
+
+        self.renderer.draw_rect(rect, theme.panel_color);
+        self.outline(rect, theme.border_width, theme.border_color);
+    }
+
+    /// Text vertically centered in `rect` and aligned horizontally inside it.
+    pub fn label(&mut self, rect: Rect, text: &str, align: TextAlign) {
+        let theme = self.ui.theme;
+
+        let style = TextStyle::new(theme.text_size)
+            .with_color(theme.text_color)
+            .with_align(align);
+
+        let [_, height] = self.renderer.measure_text(theme.font, text, &style);
+
+        let x = match align {
+            TextAlign::Left => rect.x,
+            TextAlign::Center => rect.center().x,
+            TextAlign::Right => rect.right(),
+        };
+
+        self.renderer.draw_text(theme.font, text, [x, rect.y + (rect.height - height) / 2.0], &style);
+    }
+
+    pub fn button(&mut self, rect: Rect, text: &str) -> Response {
+        let id = self.id(text);
+        let response = self.interact(id, rect);
+        let theme = self.ui.theme;
+
+        let color = if response.hovered { theme.hover_color } else { theme.button_color };
+
+        self.renderer.draw_rect(rect, color);
+        self.outline(rect, theme.border_width, theme.border_color);
+        self.focus_outline(&response);
+        self.label(rect, text, TextAlign::Center);
+
+        response
+    }
+
+    /// Bar filled from the left by `fraction`, for stamina, health and crafting progress.
+    pub fn progress_bar(&mut self, rect: Rect, fraction: f32, color: [f32; 4]) {
+        let theme = self.ui.theme;
+        let inner = rect.inset(theme.border_width);
+
+        self.renderer.draw_rect(rect, theme.bar_background);
+
+        let filled = Rect::new(inner.x, inner.y, inner.width * fraction.clamp(0.0, 1.0), inner.height);
+        self.renderer.draw_rect(filled, color);
+    }
+
+    /// Square slots in rows of `columns` starting at `position`, `None` slots are empty.
+    pub fn slot_grid(
+        &mut self,
+        id: &str,
+        position: [f32; 2],
+        columns: usize,
+        slot_size: f32,
+        slots: &[Option<SlotItem>],
+    ) -> SlotGridResponse {
+        let theme = self.ui.theme;
+        let stride = slot_size + theme.spacing;
+        let mut result = SlotGridResponse::default();
+
+        self.push_id(id);
+
+        for (index, slot) in slots.iter().enumerate() {
+            let (column, row) = (index % columns.max(1), index / columns.max(1));
+            let rect = Rect::new(position[0] + column as f32 * stride, position[1] + row as f32 * stride, slot_size, slot_size);
+
+            let id = self.id(index);
+            let response = self.interact(id, rect);
+
+            let color = if response.hovered { theme.hover_color } else { theme.bar_background };
+
+            self.renderer.draw_rect(rect, color);
+            self.outline(rect, theme.border_width, theme.border_color);
+
+            if let Some(item) = slot {
+                self.renderer.draw_image(item.texture_id, rect.inset(theme.padding), item.uv_rect, [1.0; 4]);
+
+                if item.count > 1 {
+                    let style = TextStyle::new(theme.text_size * 0.75)
+                        .with_color(theme.text_color)
+                        .with_align(TextAlign::Right);
+
+                    let [_, height] = self.renderer.measure_text(theme.font, "0", &style);
+                    let corner = [rect.right() - theme.padding / 2.0, rect.bottom() - height];
+
+                    self.renderer.draw_text(theme.font, &item.count.to_string(), corner, &style);
+                }
+            }
+
+            self.focus_outline(&response);
+
+            if response.clicked {
+                result.clicked = Some(index);
+            }
+
+            if response.is_active() {
+                result.active = Some((index, rect));
+            }
+        }
+
+        self.pop_id();
+
+        result
+    }
+
+    /// Vertical list of `count` items scrolled with the mouse wheel, only the part inside `rect` is drawn.
+    /// `item` declares the widgets of one item inside the rectangle it's given.
+    pub fn scroll_list(
+        &mut self,
+        id: &str,
+        rect: Rect,
+        item_height: f32,
+        count: usize,
+        mut item: impl FnMut(&mut Self, usize, Rect),
+    ) {
+        let list_id = self.id(id);
+        let theme = self.ui.theme;
+        let stride = item_height + theme.spacing;
+        let max_offset = (count as f32 * stride - theme.spacing - rect.height).max(0.0);
+
+        let mut offset = self.ui.scroll_offsets.get(&list_id).copied().unwrap_or(0.0);
+
+        if self.is_hovered(rect) {
+            offset -= self.scroll * stride;
+        }
+
+        let previous_clip = self.clip;
+        self.set_clip(previous_clip.map_or(Some(rect), |clip| clip.intersection(&rect)));
+        self.push_id(list_id.0);
+
+        for index in 0..count {
+            let top = index as f32 * stride;
+            let item_rect = Rect::new(rect.x, rect.y + top - offset, rect.width, item_height);
+            let first_widget = self.focusables.len();
+
+            self.push_id(index);
+            item(self, index, item_rect);
+            self.pop_id();
+
+            let focused = self.focusables[first_widget..].iter().any(|&(id, _)| Some(id) == self.ui.focus);
+
+            // Keeps the item focused with the keyboard or a gamepad in view from the next frame on.
+            if focused && self.navigated {
+                offset = offset.max(top + item_height - rect.height).min(top);
+            }
+        }
+
+        self.pop_id();
+        self.set_clip(previous_clip);
+
+        let offset = offset.clamp(0.0, max_offset);
+        self.ui.scroll_offsets.insert(list_id, offset);
+
+        if max_offset > 0.0 {
+            let content_height = max_offset + rect.height;
+            let thumb_height = rect.height * rect.height / content_height;
+            let thumb_y = rect.y + offset / max_offset * (rect.height - thumb_height);
+
+            let width = theme.border_width * 2.0;
+            self.renderer.draw_rect(Rect::new(rect.right() - width, thumb_y, width, thumb_height), theme.border_color);
+        }
+    }
+
+    /// Shows `text` in a box under `anchor` once all the widgets are drawn, the last call of a frame wins.
+    pub fn tooltip(&mut self, anchor: Rect, text: &str) {
+        self.tooltip = Some((anchor, text.to_string()));
+    }
+
+    /// Draws the tooltip on top and remembers the widgets for navigation in the next frame.
+    pub fn end(mut self) {
+        if let Some((anchor, text)) = self.tooltip.take() {
+            self.draw_tooltip(anchor, &text);
+        }
+
+        if self.ui.focus.is_some_and(|focus| !self.focusables.iter().any(|&(id, _)| id == focus)) {
+            self.ui.focus = None;
+        }
+
+        self.renderer.set_overlay_clip(None);
+        self.ui.focusables = self.focusables;
+    }
+
+    fn draw_tooltip(&mut self, anchor: Rect, text: &str) {
+        let theme = self.ui.theme;
+
+        let style = TextStyle::new(theme.text_size)
+            .with_color(theme.text_color)
+            .with_max_width(320.0);
+
+        let [width, height] = self.renderer.measure_text(theme.font, text, &style);
+        let (width, height) = (width + theme.padding * 2.0, height + theme.padding * 2.0);
+
+        // Below the anchor, or above it when it would leave the window.
+        let y = if anchor.bottom() + height <= self.screen.bottom() {
+            anchor.bottom() + theme.spacing
+        } else {
+            anchor.y - height - theme.spacing
+        };
+
+        let x = anchor.x.min(self.screen.right() - width).max(0.0);
+        let rect = Rect::new(x, y, width, height);
+
+        self.renderer.set_overlay_clip(None);
+        self.renderer.draw_rect(rect, theme.tooltip_color);
+        self.outline(rect, theme.border_width, theme.border_color);
+        self.renderer.draw_text(theme.font, text, [rect.x + theme.padding, rect.y + theme.padding], &style);
+    }
+
+    fn id(&self, key: impl Hash) -> WidgetId {
+        WidgetId::new(*self.id_stack.last().unwrap_or(&0), key)
+    }
+
+    fn is_hovered(&self, rect: Rect) -> bool {
+        let visible = self.clip.map_or(Some(rect), |clip| rect.intersection(&clip));
+        visible.zip(self.cursor).is_some_and(|(visible, cursor)| visible.contains(cursor))
+    }
+
+    /// Registers a focusable widget, clicking it also focuses it.
+    fn interact(&mut self, id: WidgetId, rect: Rect) -> Response {
+        let hovered = self.is_hovered(rect);
+
+        if hovered && self.clicked {
+            self.ui.focus = Some(id);
+        }
+
+        self.focusables.push((id, rect));
+
+        let focused = self.ui.focus == Some(id);
+
+        Response {
+            rect,
+            hovered,
+            focused,
+            clicked: (hovered && self.clicked) || (focused && self.activate),
+        }
+    }
+
+    fn set_clip(&mut self, clip: Option<Rect>) {
+        self.clip = clip;
+        self.renderer.set_overlay_clip(clip);
+    }
+
+    fn outline(&mut self, rect: Rect, width: f32, color: [f32; 4]) {
+        self.renderer.draw_rect(Rect::new(rect.x, rect.y, rect.width, width), color);
+        self.renderer.draw_rect(Rect::new(rect.x, rect.bottom() - width, rect.width, width), color);
+        self.renderer.draw_rect(Rect::new(rect.x, rect.y + width, width, rect.height - width * 2.0), color);
+        self.renderer.draw_rect(Rect::new(rect.right() - width, rect.y + width, width, rect.height - width * 2.0), color);
+    }
+
+    fn focus_outline(&mut self, response: &Response) {
+        if response.focused {
+            let theme = self.ui.theme;
+            self.outline(response.rect, theme.border_width, theme.focus_color);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn anchor_place() {
+        let parent = Rect::new(0.0, 0.0, 800.0, 600.0);
+
+        assert_eq!(Anchor::TopLeft.place(&parent, 100.0, 50.0, [10.0, 20.0]), Rect::new(10.0, 20.0, 100.0, 50.0));
+        assert_eq!(Anchor::Center.place(&parent, 100.0, 50.0, [10.0, 20.0]), Rect::new(350.0, 275.0, 100.0, 50.0));
+        assert_eq!(Anchor::BottomRight.place(&parent, 100.0, 50.0, [10.0, 20.0]), Rect::new(690.0, 530.0, 100.0, 50.0));
+
+        let offset = Rect::new(100.0, 200.0, 400.0, 300.0);
+        assert_eq!(Anchor::Bottom.place(&offset, 100.0, 50.0, [0.0, 10.0]), Rect::new(250.0, 440.0, 100.0, 50.0));
+    }
+
+    /// Ui with a 3 by 2 grid of widgets, numbered row by row.
+    fn grid() -> Ui {
+        let mut ui = Ui::new(UiTheme::new(0));
+
+        ui.focusables = (0..6)
+            .map(|index| {
+                let (column, row) = (index % 3, index / 3);
+                (WidgetId::new(0, index), Rect::new(column as f32 * 50.0, row as f32 * 50.0, 40.0, 40.0))
+            })
+            .collect();
+
+        ui
+    }
+
+    fn focused(ui: &Ui) -> Option<usize> {
+        ui.focusables.iter().position(|&(id, _)| Some(id) == ui.focus)
+    }
+
+    #[test]
+    fn first_move_focuses_the_first_widget() {
+        let mut ui = grid();
+
+        assert!(!ui.move_focus(NavAction::Activate));
+        assert_eq!(focused(&ui), None);
+
+        assert!(ui.move_focus(NavAction::Down));
+        assert_eq!(focused(&ui), Some(0));
+    }
+
+    #[test]
+    fn next_and_previous_wrap_around() {
+        let mut ui = grid();
+        ui.focus = Some(ui.focusables[5].0);
+
+        ui.move_focus(NavAction::Next);
+        assert_eq!(focused(&ui), Some(0));
+
+        ui.move_focus(NavAction::Previous);
+        assert_eq!(focused(&ui), Some(5));
+    }
+
+    #[test]
+    fn directions_move_through_the_grid() {
+        let mut ui = grid();
+        ui.focus = Some(ui.focusables[0].0);
+
+        assert!(ui.move_focus(NavAction::Right));
+        assert_eq!(focused(&ui), Some(1));
+
+        assert!(ui.move_focus(NavAction::Down));
+        assert_eq!(focused(&ui), Some(4));
+
+        assert!(ui.move_focus(NavAction::Left));
+        assert_eq!(focused(&ui), Some(3));
+
+        // Nothing further down, the focus stays.
+        assert!(!ui.move_focus(NavAction::Down));
+        assert_eq!(focused(&ui), Some(3));
+    }
+
+    #[test]
+    fn no_widgets_no_focus() {
+        let mut ui = Ui::new(UiTheme::new(0));
+
+        assert!(!ui.move_focus(NavAction::Next));
+        assert_eq!(ui.focus, None);
+    }
+}
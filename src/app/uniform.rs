@@ -0,0 +1,75 @@
+use wgpu::BufferAddress;
+use wgpu::util::DeviceExt;
+
+pub struct Uniform<T: bytemuck::Pod> {
+    pub value: T,
+    pub buffer: wgpu::Buffer,
+    pub bind_group: wgpu::BindGroup,
+}
+
+impl<T: bytemuck::Pod> Uniform<T> {
+    pub fn new(device: &wgpu::Device, value: T, visibility: wgpu::ShaderStages, label: &str) -> Self {
+        let layout = Self::create_bind_group_layout(device, visibility, label);
+
+        let buffer = device.create_buffer_init(
+            &wgpu::util::BufferInitDescriptor {
+                label: Some(label),
+                contents: bytemuck::bytes_of(&value),
+                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            }
+        );
+
+        let bind_group = device.create_bind_group(
+            &wgpu::BindGroupDescriptor {
+                label: Some(label),
+                layout: &layout,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: buffer.as_entire_binding(),
+                    }
+                ],
+            }
+        );
+
+        Self {
+            value,
+            buffer,
+            bind_group,
+        }
+    }
+
+    pub fn update(&mut self, value: T, queue: &wgpu::Queue) {
+        self.value = value;
+
+        queue.write_buffer(
+            &self.buffer,
+            BufferAddress::default(),
+            bytemuck::bytes_of(&self.value),
+        );
+    }
+
+    pub fn create_bind_group_layout(
+        device: &wgpu::Device,
+        visibility: wgpu::ShaderStages,
+        label: &str,
+    ) -> wgpu::BindGroupLayout {
+        device.create_bind_group_layout(
+            &wgpu::BindGroupLayoutDescriptor {
+                label: Some(label),
+                entries: &[
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
+                ],
+            }
+        )
+    }
+}
@@ -0,0 +1,71 @@
+use crate::app::camera::Camera;
+
+/// Region of the window in normalized coordinates, `(0, 0)` is the top left corner.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Viewport {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+}
+
+impl Viewport {
+    pub const FULL: Viewport = Viewport::new(0.0, 0.0, 1.0, 1.0);
+
+    pub const fn new(x: f32, y: f32, width: f32, height: f32) -> Self {
+        Self { x, y, width, height }
+    }
+
+    /// Column `index` of `count` equal side-by-side viewports, for split-screen.
+    pub fn split_vertical(index: u32, count: u32) -> Self {
+        let width = 1.0 / count as f32;
+        Self::new(width * index as f32, 0.0, width, 1.0)
+    }
+
+    /// Row `index` of `count` equal stacked viewports, for split-screen.
+    pub fn split_horizontal(index: u32, count: u32) -> Self {
+        let height = 1.0 / count as f32;
+        Self::new(0.0, height * index as f32, 1.0, height)
+    }
+
+    /// `[x, y, width, height]` in pixels of a `width` x `height` target.
+    pub fn to_pixels(&self, width: u32, height: u32) -> [f32; 4] {
+        let (width, height) = (width as f32, height as f32);
+
+        [
+            (self.x * width).floor(),
+            (self.y * height).floor(),
+            (self.width * width).floor().max(1.0),
+            (self.height * height).floor().max(1.0),
+        ]
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum ViewTarget {
+    /// Drawn on the window after the main camera, e.g. picture-in-picture or split-screen.
+    Screen(Viewport),
+    /// Drawn into the render target with this texture id before the window is rendered,
+    /// so the texture can be used by draw calls in the same frame (minimap, barn monitor).
+    Texture(usize),
+}
+
+/// Additional camera rendering the same draw calls as the main one.
+#[derive(Copy, Clone)]
+pub struct View {
+    pub camera: Camera,
+    pub target: ViewTarget,
+    pub clear_color: wgpu::Color,
+    pub enabled: bool,
+}
+
+impl View {
+    pub fn new(camera: Camera, target: ViewTarget) -> Self {
+        Self {
+            camera,
+            target,
+            clear_color: wgpu::Color::BLACK,
+            enabled: true,
+        }
+    }
+}
@@ -1,22 +1,32 @@
-mod app;
+pub mod app;
 
-use cgmath::{SquareMatrix, Vector4};
+use cgmath::{Point3, SquareMatrix, Vector4};
 use app::App;
 
-
-use winit::{
-    event_loop::EventLoop,
-    window::WindowBuilder,
-};
 use winit::keyboard::{KeyCode, PhysicalKey};
+use crate::app::animation::{AnimationClip, Animator, LoopMode};
 use crate::app::buffers::{INDICES, VERTICES};
 use crate::app::context::{DrawCall, DrawParams, Renderer};
+use crate::app::camera_controller::OrbitController;
+use crate::app::geometry::{Aabb, Rect};
+use crate::app::light::PointLight;
+use crate::app::render_mode::RenderMode;
+use crate::app::text::{TextAlign, TextStyle};
+use crate::app::ui::{Anchor, SlotItem, Ui, UiTheme};
 use crate::app::GameLogic;
 
 struct TestLogic {
     textures: Vec<usize>,
     mesh: usize,
+    font: Option<usize>,
+    ui: Option<Ui>,
+    menu_open: bool,
+    elapsed: f32,
+    animator: Option<Animator>,
     size: i32,
+    camera: Option<OrbitController>,
+    debug: bool,
+    next_render_mode: bool,
 }
 
 impl TestLogic {
@@ -24,13 +34,107 @@ impl TestLogic {
         Self {
             textures: Vec::with_capacity(2),
             mesh: 0,
+            font: None,
+            ui: None,
+            menu_open: false,
+            elapsed: 0.0,
+            animator: None,
             size: 0,
+            camera: None,
+            debug: false,
+            next_render_mode: false,
         }
     }
 }
 
+impl TestLogic {
+    fn render_sprites(&mut self, renderer: &mut Renderer) {
+        self.elapsed += renderer.delta_time();
+
+        let white = [1.0; 4];
+
+        if let Some(animator) = &mut self.animator {
+            for event in animator.update(renderer.delta_time()) {
+                log::debug!("Animation event {event}");
+            }
+
+            // Queued top layer first, the layers still draw the stone over the grass.
+            let source = Some(animator.source_rect());
+            renderer.draw_sprite(self.textures[1], source, Rect::new(56.0, 120.0, 64.0, 64.0), self.elapsed, white, 1);
+        }
+
+        renderer.draw_sprite(self.textures[0], None, Rect::new(16.0, 104.0, 96.0, 96.0), 0.0, [1.0, 1.0, 1.0, 0.8], 0);
+    }
+
+    fn render_ui(&mut self, renderer: &mut Renderer) {
+        let Some(ui) = &mut self.ui else {
+            return;
+        };
+
+        let mut ui = ui.begin(renderer);
+
+        let slots = [
+            Some(SlotItem::new(self.textures[0]).with_count(12)),
+            Some(SlotItem::new(self.textures[1]).with_count(3)),
+            None,
+            None,
+            None,
+        ];
+
+        let hotbar = ui.anchored(Anchor::Bottom, 5.0 * 52.0, 48.0, [0.0, 16.0]);
+        let grid = ui.slot_grid("hotbar", [hotbar.x, hotbar.y], slots.len(), 48.0, &slots);
+
+        if let Some((index @ 0..=1, rect)) = grid.active {
+            ui.tooltip(rect, ["Grass\nGrows back every spring.", "Stone\nUsed for paths and fences."][index]);
+        }
+
+        let stamina = ui.anchored(Anchor::BottomLeft, 200.0, 16.0, [16.0, 16.0]);
+        ui.progress_bar(stamina, 0.7, [0.3, 0.8, 0.3, 1.0]);
+
+        if self.menu_open {
+            let panel = ui.anchored(Anchor::Center, 280.0, 320.0, [0.0, 0.0]);
+            ui.panel(panel);
+
+            let content = panel.inset(ui.theme().padding * 2.0);
+            let (title, content) = content.split_top(24.0, 8.0);
+            ui.label(title, &format!("Size {}", self.size), TextAlign::Center);
+
+            let (bigger, content) = content.split_top(32.0, 4.0);
+            let (smaller, content) = content.split_top(32.0, 8.0);
+
+            if ui.button(bigger, "Bigger").clicked {
+                self.size += 1;
+            }
+
+            if ui.button(smaller, "Smaller").clicked {
+                self.size -= 1;
+            }
+
+            let mut selected = None;
+
+            ui.scroll_list("render modes", content, 28.0, RenderMode::ALL.len(), |ui, index, rect| {
+                let mode = RenderMode::ALL[index];
+
+                if ui.button(Rect { width: rect.width - 8.0, ..rect }, &format!("{mode:?}")).clicked {
+                    selected = Some(mode);
+                }
+            });
+
+            if ui.back_pressed() {
+                self.menu_open = false;
+            }
+
+            if let Some(mode) = selected {
+                ui.renderer().set_render_mode(mode);
+            }
+        }
+
+        ui.end();
+    }
+}
+
 impl GameLogic for TestLogic {
-    fn init<'a, 'b>(&'a mut self, renderer: &'b mut Renderer<'a>) where 'a: 'b {
+    fn init(&mut self, renderer: &mut Renderer) {
         self.mesh = renderer.add_mesh(VERTICES, INDICES);
 
         let first_texture = renderer.add_texture("resources/grass.jpeg")
@@ -41,15 +145,78 @@ impl GameLogic for TestLogic {
 
         self.textures.push(first_texture);
         self.textures.push(second_texture);
+
+        // Stands in for a sprite sheet: the quarters of the stone texture, top row then bottom row.
+        let (width, height) = renderer.texture_size(second_texture);
+        let quarter = Rect::new(0.0, 0.0, width as f32 / 2.0, height as f32 / 2.0);
+
+        let clip = AnimationClip::from_strip(quarter, 2, 0.25, LoopMode::PingPong)
+            .with_frame(quarter.translate(quarter.width, quarter.height), 0.5)
+            .with_frame(quarter.translate(0.0, quarter.height), 0.25)
+            .with_event(2, "bottom row");
+
+        self.animator = Some(Animator::new(clip));
+
+        self.font = renderer.add_font("resources/Cantarell-Regular.ttf", 32.0);
+        self.ui = self.font.map(|font| Ui::new(UiTheme::new(font)));
+
+        self.camera = Some(OrbitController::from_camera(renderer.camera()));
+
+        renderer.lights_mut().points.push(PointLight::new(
+            cgmath::Point3::new(0.0, 2.0, 2.0),
+            [1.0, 0.7, 0.4],
+            8.0,
+            10.0,
+        ));
     }
 
-    fn render<'a, 'b>(&'a mut self, renderer: &'b mut Renderer<'a>) where 'a: 'b {
+    fn render(&mut self, renderer: &mut Renderer) {
+        if let Some(camera) = &mut self.camera {
+            renderer.update_camera(camera);
+        }
+
+        if std::mem::take(&mut self.next_render_mode) {
+            let mut mode = renderer.render_mode().next();
+
+            if !renderer.supports_render_mode(mode) {
+                mode = mode.next();
+            }
+
+            renderer.set_render_mode(mode);
+        }
+
+        if let Some(font) = self.font {
+            let clock = renderer.clock();
+            let time = format!("Day {}\n{:02}:{:02}", clock.day(), clock.hour(), clock.minute());
+            let width = renderer.input().window_size().width as f32;
+
+            let style = TextStyle::new(24.0).with_align(TextAlign::Right);
+            renderer.draw_text(font, &time, [width - 16.0, 16.0], &style);
+
+            let help = "A and D change the size, F1 opens the menu, F3 toggles debug lines and F4 cycles the render mode";
+            renderer.draw_text(font, help, [16.0, 16.0], &TextStyle::new(16.0).with_max_width(240.0));
+        }
+
+        self.render_sprites(renderer);
+        self.render_ui(renderer);
+
         let mut matrix = cgmath::Matrix4::<f32>::identity();
 
         const DISTANCE : f32 = 1.25;
 
         let size = self.size;
 
+        let debug = renderer.debug();
+        debug.set_enabled(self.debug);
+
+        let extent = DISTANCE * size as f32 + 0.5;
+        let bounds = Aabb::new(Point3::new(-extent, -extent, -extent), Point3::new(extent, extent, extent));
+
+        debug.grid(Point3::new(0.0, -extent, 0.0), size.max(1) as u32 * 2, DISTANCE / 2.0, [0.5, 0.5, 0.5, 0.5]);
+        debug.aabb(&bounds, [1.0, 1.0, 0.0, 1.0]);
+        debug.axes(&matrix, 1.0);
+        debug.text_3d(Point3::new(0.0, extent + 0.5, 0.0), &format!("SIZE {size}"), 0.4, [1.0, 1.0, 1.0, 1.0]);
+
         for x in -size..=size {
             for y in -size..=size {
                 for z in -size..=size {
@@ -60,15 +227,12 @@ impl GameLogic for TestLogic {
                         1.0,
                     );
 
-                    let params = DrawParams {
-                        mesh_id: self.mesh,
-                        texture_id: self.textures[(x + y + z) as usize % self.textures.len()],
-                    };
+                    let params = DrawParams::new(
+                        self.mesh,
+                        self.textures[(x + y + z) as usize % self.textures.len()],
+                    );
 
-                    renderer.draw(DrawCall {
-                        params,
-                        matrix,
-                    });
+                    renderer.draw(DrawCall::new(params, matrix));
                 }
             }
         }
@@ -79,6 +243,9 @@ impl GameLogic for TestLogic {
             match input {
                 PhysicalKey::Code(KeyCode::KeyA) => self.size -= 1,
                 PhysicalKey::Code(KeyCode::KeyD) => self.size += 1,
+                PhysicalKey::Code(KeyCode::F3) => self.debug = !self.debug,
+                PhysicalKey::Code(KeyCode::F4) => self.next_render_mode = true,
+                PhysicalKey::Code(KeyCode::F1) => self.menu_open = !self.menu_open,
                 _ => {},
             }
         }
@@ -89,17 +256,9 @@ impl GameLogic for TestLogic {
 pub async fn run() {
     env_logger::init();
 
-    let event_loop = EventLoop::new().unwrap();
-    let window =
-        WindowBuilder::new()
-        .build(&event_loop)
-        .unwrap();
-
-    let mut test_logic = TestLogic::new();
-
-    let mut app = App::new(window, &mut test_logic).await;
-
-    event_loop.run(move |event, elwt|{
-        app.main_loop(event, elwt);
-    }).unwrap();
+    App::builder(Box::new(TestLogic::new()))
+        .title("RPG Farm")
+        .settings_file("settings.cfg")
+        .run()
+        .await;
 }
\ No newline at end of file